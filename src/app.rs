@@ -1,30 +1,50 @@
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::KeyEvent;
+use ratatui::layout::Direction;
 use ratatui::symbols::line;
 
-use crate::parsing::FormattedSpan;
-use crate::styles::Theme;
-use crate::utils::clargs::Args;
-use crate::utils::{create_shared, remainder, shared_copy};
-use crate::wikipedia::{self, SearchResult};
+use crate::article_layout::ArticleLayout;
+use crate::bookmarks::{Bookmark, BookmarkStore};
+use crate::history::HistoryLog;
+use crate::keymap::{typed_char, Action, Keymap};
+use crate::parsing::{FormattedSpan, InfoboxEntry};
+use crate::session::{SearchSession, Session, TabSession};
+use crate::styles::{
+    color_to_string, load_themes, parse_color_input, parse_style, style_to_string, ColorSupport,
+    Theme,
+};
+use crate::utils::clargs::{save_theme_to_config, Args};
+use crate::utils::{create_shared, fuzzy_match_score, remainder, shared_copy};
+use crate::wikipedia::{self, SearchResult, SharedArticle, WikiSource};
 use crate::{caching::CachingSession, utils::Shared};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
+use std::cell::{Cell, RefCell};
 use std::char;
 use std::collections::VecDeque;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::rc::Rc;
 use std::sync::Arc;
 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum AppState {
     Title,
     Search,
     SearchMenu,
     Article,
+    ArticleSearch,
+    LinkHints,
     ArticleMenu,
+    Contents,
     Credit,
     ThemeMenu,
+    CommandPalette,
+    Bookmarks,
+    History,
+    ThemeEditor,
 }
 pub type AppAction = Arc<dyn Fn(&mut App) + Send + Sync>;
 
+#[derive(Clone)]
 pub struct ActionItem {
     label: String,
     action: AppAction,
@@ -86,12 +106,12 @@ pub trait ActionMenu {
         selected_option.action_clone()
     }
 
-    fn handle_key(&mut self, keyevent: KeyEvent) {
-        match keyevent.code {
-            KeyCode::Up => {
+    fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::SelectPrevious => {
                 self.scroll(ScrollDirection::UP);
             }
-            KeyCode::Down => {
+            Action::SelectNext => {
                 self.scroll(ScrollDirection::DOWN);
             }
             _ => {}
@@ -99,14 +119,53 @@ pub trait ActionMenu {
     }
 }
 
+/// A text input whose cursor is a byte index into the input, always kept on a character boundary
 pub trait TypeableState {
     fn get_input(&self) -> String;
-    fn insert_to_input_at_cursor(&mut self, c: char) -> ();
-    fn remove_from_input_at_cursor(&mut self) -> ();
+    fn input_mut(&mut self) -> &mut String;
     fn get_cursor_pos(&self) -> usize;
     fn set_cursor_pos(&mut self, new_cursor_pos: usize) -> ();
     fn trigger_text_focus(&mut self) -> () {}
 
+    fn insert_to_input_at_cursor(&mut self, c: char) -> () {
+        let cursor_pos = self.get_cursor_pos();
+        let input = self.input_mut();
+        if input.is_char_boundary(cursor_pos) {
+            input.insert(cursor_pos, c);
+        }
+    }
+
+    /// Removes the character just before the cursor, leaving the cursor where it is
+    fn remove_from_input_at_cursor(&mut self) -> () {
+        let cursor_pos = self.get_cursor_pos();
+        let input = self.input_mut();
+        if let Some((char_start, _)) = input
+            .get(..cursor_pos)
+            .and_then(|before_cursor| before_cursor.char_indices().next_back())
+        {
+            input.remove(char_start);
+        }
+    }
+
+    /// Where the cursor would be one character along in `cursor_direction`
+    fn cursor_pos_one_step(&self, cursor_direction: CursorDirection) -> usize {
+        let input = self.get_input();
+        let cursor_pos = self.get_cursor_pos().min(input.len());
+        let step = match cursor_direction {
+            CursorDirection::LEFT => input
+                .get(..cursor_pos)
+                .and_then(|before_cursor| before_cursor.chars().next_back()),
+            CursorDirection::RIGHT => input
+                .get(cursor_pos..)
+                .and_then(|after_cursor| after_cursor.chars().next()),
+        }
+        .map_or(0, char::len_utf8);
+        match cursor_direction {
+            CursorDirection::LEFT => cursor_pos - step,
+            CursorDirection::RIGHT => cursor_pos + step,
+        }
+    }
+
     fn move_cursor_to_start(&mut self) -> () {
         self.set_cursor_pos(0);
         self.trigger_text_focus();
@@ -118,23 +177,12 @@ pub trait TypeableState {
     }
 
     fn move_cursor_one_step(&mut self, cursor_direction: CursorDirection) {
-        // Limit cursor_pos to be between 0 and (length of the input) - 1
-        match cursor_direction {
-            CursorDirection::LEFT => {
-                self.set_cursor_pos(self.get_cursor_pos().saturating_sub(1));
-            }
-            CursorDirection::RIGHT => {
-                if self.get_cursor_pos() < self.get_input().len() {
-                    self.set_cursor_pos(self.get_cursor_pos() + 1);
-                }
-            }
-        }
-        self.set_cursor_pos(self.get_cursor_pos().clamp(0, self.get_input().len() + 1));
+        self.set_cursor_pos(self.cursor_pos_one_step(cursor_direction));
         self.trigger_text_focus();
     }
 
     fn type_char(&mut self, c: char) {
-        if !(self.get_cursor_pos() > self.get_input().len()) {
+        if self.get_cursor_pos() <= self.get_input().len() {
             self.insert_to_input_at_cursor(c);
             self.move_cursor_one_step(CursorDirection::RIGHT);
         }
@@ -142,33 +190,36 @@ pub trait TypeableState {
     }
 
     fn backspace(&mut self) {
-        if !(self.get_input().is_empty()) {
-            if self.get_cursor_pos() > 0 {
-                self.remove_from_input_at_cursor();
-                self.move_cursor_one_step(CursorDirection::LEFT);
-            }
+        let previous_cursor_pos = self.cursor_pos_one_step(CursorDirection::LEFT);
+        if previous_cursor_pos < self.get_cursor_pos() {
+            self.remove_from_input_at_cursor();
+            self.set_cursor_pos(previous_cursor_pos);
         }
         self.trigger_text_focus();
     }
-    fn handle_key(&mut self, keyevent: KeyEvent) {
-        match keyevent.code {
-            KeyCode::Char(c) => {
-                // Append character to input
-                self.type_char(c);
+
+    /// Edits the input for a bound text editing action, or types the key if it is unbound
+    fn handle_key(&mut self, action: Option<Action>, keyevent: KeyEvent) {
+        match action {
+            None => {
+                if let Some(c) = typed_char(keyevent) {
+                    // Append character to input
+                    self.type_char(c);
+                }
             }
-            KeyCode::Backspace => {
+            Some(Action::DeleteBackward) => {
                 self.backspace();
             }
-            KeyCode::Left if keyevent.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::CursorToStart) => {
                 self.move_cursor_to_start();
             }
-            KeyCode::Left => {
+            Some(Action::CursorLeft) => {
                 self.move_cursor_one_step(CursorDirection::LEFT);
             }
-            KeyCode::Right if keyevent.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::CursorToEnd) => {
                 self.move_cursor_to_end();
             }
-            KeyCode::Right => {
+            Some(Action::CursorRight) => {
                 self.move_cursor_one_step(CursorDirection::RIGHT);
             }
             _ => {}
@@ -186,14 +237,8 @@ impl TypeableState for TitleState {
         self.input.clone()
     }
 
-    fn insert_to_input_at_cursor(&mut self, c: char) -> () {
-        self.input.insert(self.cursor_pos, c);
-    }
-
-    fn remove_from_input_at_cursor(&mut self) -> () {
-        if self.cursor_pos <= self.input.len() && self.cursor_pos > 0 {
-            self.input.remove(self.cursor_pos - 1);
-        }
+    fn input_mut(&mut self) -> &mut String {
+        &mut self.input
     }
 
     fn get_cursor_pos(&self) -> usize {
@@ -255,14 +300,8 @@ impl TypeableState for SearchState {
         self.input.clone()
     }
 
-    fn insert_to_input_at_cursor(&mut self, c: char) -> () {
-        self.input.insert(self.cursor_pos, c);
-    }
-
-    fn remove_from_input_at_cursor(&mut self) -> () {
-        if self.cursor_pos <= self.input.len() && self.cursor_pos > 0 {
-            self.input.remove(self.cursor_pos - 1);
-        }
+    fn input_mut(&mut self) -> &mut String {
+        &mut self.input
     }
 
     fn get_cursor_pos(&self) -> usize {
@@ -277,6 +316,140 @@ impl TypeableState for SearchState {
         self.text_box_is_highlighted = true;
     }
 }
+/// A piece of an article span that matches the in-article search, as a byte range of its text
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextMatch {
+    pub span_index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+pub struct ArticleSearchState {
+    pub input: String,
+    pub cursor_pos: usize,
+    pub matches: Vec<TextMatch>,
+    pub current_match: usize,
+}
+
+impl ArticleSearchState {
+    pub fn is_active(&self) -> bool {
+        !self.input.is_empty()
+    }
+
+    pub fn current(&self) -> Option<TextMatch> {
+        self.matches.get(self.current_match).copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.input.clear();
+        self.cursor_pos = 0;
+        self.matches.clear();
+        self.current_match = 0;
+    }
+
+    /// Finds every case-insensitive occurrence of the input across the article's spans
+    pub fn find_matches(&mut self, spans: &[FormattedSpan]) {
+        self.matches.clear();
+        self.current_match = 0;
+        if self.input.is_empty() {
+            return;
+        }
+        let query_regex = match Regex::new(&format!("(?i){}", regex::escape(&self.input))) {
+            Ok(query_regex) => query_regex,
+            Err(_) => return,
+        };
+        for formatted_span in spans
+            .iter()
+            .filter(|formatted_span| formatted_span.table.is_none())
+        {
+            for found in query_regex.find_iter(&formatted_span.text) {
+                self.matches.push(TextMatch {
+                    span_index: formatted_span.index,
+                    start: found.start(),
+                    end: found.end(),
+                });
+            }
+        }
+    }
+
+    pub fn scroll_match(&mut self, direction: ScrollDirection) {
+        let total_matches = self.matches.len();
+        if total_matches == 0 {
+            return;
+        }
+        self.current_match = match direction {
+            ScrollDirection::UP => remainder(self.current_match + total_matches - 1, total_matches),
+            ScrollDirection::DOWN => remainder(self.current_match + 1, total_matches),
+        };
+    }
+}
+
+impl TypeableState for ArticleSearchState {
+    fn get_input(&self) -> String {
+        self.input.clone()
+    }
+
+    fn input_mut(&mut self) -> &mut String {
+        &mut self.input
+    }
+
+    fn get_cursor_pos(&self) -> usize {
+        self.cursor_pos
+    }
+
+    fn set_cursor_pos(&mut self, new_cursor_pos: usize) {
+        self.cursor_pos = new_cursor_pos;
+    }
+}
+
+// Home row first, so the shortest hints are the easiest to type
+const HINT_CHARACTERS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// A label typed to follow one of the links on screen
+pub struct LinkHint {
+    pub label: String,
+    pub link_index: usize,
+    pub span_index: usize,
+}
+
+pub struct LinkHintState {
+    pub hints: Vec<LinkHint>,
+    pub typed: String,
+}
+
+impl LinkHintState {
+    pub fn matching_hints(&self) -> impl Iterator<Item = &LinkHint> {
+        self.hints
+            .iter()
+            .filter(|hint| hint.label.starts_with(&self.typed))
+    }
+}
+
+/// The title of the article a link in the page points to
+pub fn link_target_title(link: &str) -> String {
+    link.replace("_", " ").replace("./", "")
+}
+
+/// Labels for `count` hints, all the same length so that none is a prefix of another
+fn hint_labels(count: usize) -> Vec<String> {
+    let characters: Vec<char> = HINT_CHARACTERS.chars().collect();
+    let mut label_length = 1;
+    while characters.len().pow(label_length) < count {
+        label_length += 1;
+    }
+    (0..count)
+        .map(|hint_number| {
+            let mut label = String::new();
+            let mut remaining = hint_number;
+            for _ in 0..label_length {
+                label.insert(0, characters[remaining % characters.len()]);
+                remaining /= characters.len();
+            }
+            label
+        })
+        .collect()
+}
+
 pub struct MenuState {
     pub selected_index: usize,
     pub options: Vec<ActionItem>,
@@ -300,6 +473,220 @@ impl ActionMenu for MenuState {
     }
 }
 
+pub struct CommandPaletteState {
+    pub input: String,
+    pub cursor_pos: usize,
+    /// Every action on offer when the palette was opened
+    pub items: Vec<ActionItem>,
+    /// The items matching the input, best match first
+    pub filtered_items: Vec<ActionItem>,
+    pub selected_index: usize,
+    /// Screen to go back to once the palette closes
+    pub return_state: AppState,
+}
+
+impl CommandPaletteState {
+    pub fn filter_items(&mut self) {
+        let mut scored_items: Vec<(i64, &ActionItem)> = self
+            .items
+            .iter()
+            .filter_map(|item| {
+                fuzzy_match_score(&self.input, item.label()).map(|score| (score, item))
+            })
+            .collect();
+        scored_items.sort_by_key(|(score, _)| -score);
+        self.filtered_items = scored_items
+            .into_iter()
+            .map(|(_, item)| item.clone())
+            .collect();
+        self.selected_index = 0;
+    }
+}
+
+impl TypeableState for CommandPaletteState {
+    fn get_input(&self) -> String {
+        self.input.clone()
+    }
+
+    fn input_mut(&mut self) -> &mut String {
+        &mut self.input
+    }
+
+    fn get_cursor_pos(&self) -> usize {
+        self.cursor_pos
+    }
+
+    fn set_cursor_pos(&mut self, new_cursor_pos: usize) {
+        self.cursor_pos = new_cursor_pos;
+    }
+}
+
+impl ActionMenu for CommandPaletteState {
+    fn total_options(&self) -> usize {
+        self.filtered_items.len()
+    }
+
+    fn set_index(&mut self, new_index: usize) {
+        self.selected_index = new_index;
+    }
+
+    fn get_index(&self) -> usize {
+        self.selected_index
+    }
+
+    fn get_options(&self) -> &Vec<ActionItem> {
+        &self.filtered_items
+    }
+}
+
+pub struct BookmarksState {
+    pub store: BookmarkStore,
+    pub filter: String,
+    pub cursor_pos: usize,
+    /// Position in the filtered list
+    pub selected_index: usize,
+    /// The bookmark whose tags are being typed in, if any
+    pub editing_tags: Option<usize>,
+    pub tags_input: String,
+    /// Screen to go back to once the bookmarks screen closes
+    pub return_state: AppState,
+}
+
+impl BookmarksState {
+    pub fn filtered_indices(&self) -> Vec<usize> {
+        self.store.filtered_indices(&self.filter)
+    }
+
+    /// Index into the store of the highlighted bookmark
+    pub fn selected_bookmark_index(&self) -> Option<usize> {
+        self.filtered_indices().get(self.selected_index).copied()
+    }
+
+    pub fn scroll_bookmarks(&mut self, scroll_direction: ScrollDirection) {
+        let total_bookmarks = self.filtered_indices().len();
+        if total_bookmarks == 0 {
+            return;
+        }
+        self.selected_index = match scroll_direction {
+            ScrollDirection::UP => {
+                remainder(self.selected_index + total_bookmarks - 1, total_bookmarks)
+            }
+            ScrollDirection::DOWN => remainder(self.selected_index + 1, total_bookmarks),
+        };
+    }
+
+    fn active_input(&mut self) -> &mut String {
+        match self.editing_tags {
+            Some(_) => &mut self.tags_input,
+            None => &mut self.filter,
+        }
+    }
+
+    pub fn start_editing_tags(&mut self) {
+        if let Some(bookmark_index) = self.selected_bookmark_index() {
+            self.tags_input = self.store.bookmarks[bookmark_index].tags.join(", ");
+            self.cursor_pos = self.tags_input.len();
+            self.editing_tags = Some(bookmark_index);
+        }
+    }
+
+    /// Leaves tag editing, saving the comma separated tags typed in if `save` is set
+    pub fn finish_editing_tags(&mut self, save: bool) {
+        if let (Some(bookmark_index), true) = (self.editing_tags, save) {
+            let tags = self
+                .tags_input
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect();
+            self.store.set_tags(bookmark_index, tags).unwrap_or(());
+        }
+        self.editing_tags = None;
+        self.cursor_pos = self.filter.len();
+    }
+
+    pub fn delete_selected(&mut self) {
+        if let Some(bookmark_index) = self.selected_bookmark_index() {
+            self.store.remove(bookmark_index).unwrap_or(());
+            let total_bookmarks = self.filtered_indices().len();
+            self.selected_index = self.selected_index.min(total_bookmarks.saturating_sub(1));
+        }
+    }
+}
+
+impl TypeableState for BookmarksState {
+    fn get_input(&self) -> String {
+        match self.editing_tags {
+            Some(_) => self.tags_input.clone(),
+            None => self.filter.clone(),
+        }
+    }
+
+    fn input_mut(&mut self) -> &mut String {
+        self.active_input()
+    }
+
+    fn get_cursor_pos(&self) -> usize {
+        self.cursor_pos
+    }
+
+    fn set_cursor_pos(&mut self, new_cursor_pos: usize) {
+        self.cursor_pos = new_cursor_pos;
+    }
+}
+
+pub struct HistoryState {
+    pub log: HistoryLog,
+    pub filter: String,
+    pub cursor_pos: usize,
+    /// Position in the filtered list
+    pub selected_index: usize,
+    /// Screen to go back to once the history screen closes
+    pub return_state: AppState,
+}
+
+impl HistoryState {
+    pub fn filtered_indices(&self) -> Vec<usize> {
+        self.log.filtered_indices(&self.filter)
+    }
+
+    /// Index into the log of the highlighted entry
+    pub fn selected_entry_index(&self) -> Option<usize> {
+        self.filtered_indices().get(self.selected_index).copied()
+    }
+
+    pub fn scroll_entries(&mut self, scroll_direction: ScrollDirection) {
+        let total_entries = self.filtered_indices().len();
+        if total_entries == 0 {
+            return;
+        }
+        self.selected_index = match scroll_direction {
+            ScrollDirection::UP => {
+                remainder(self.selected_index + total_entries - 1, total_entries)
+            }
+            ScrollDirection::DOWN => remainder(self.selected_index + 1, total_entries),
+        };
+    }
+}
+
+impl TypeableState for HistoryState {
+    fn get_input(&self) -> String {
+        self.filter.clone()
+    }
+
+    fn input_mut(&mut self) -> &mut String {
+        &mut self.filter
+    }
+
+    fn get_cursor_pos(&self) -> usize {
+        self.cursor_pos
+    }
+
+    fn set_cursor_pos(&mut self, new_cursor_pos: usize) {
+        self.cursor_pos = new_cursor_pos;
+    }
+}
+
 pub struct CreditState {
     pub selected_index: usize,
     pub options: Vec<ActionItem>,
@@ -323,19 +710,114 @@ impl ActionMenu for CreditState {
     }
 }
 
+/// Where the reader was in an article, so it can be put back when the page is revisited
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArticleView {
+    pub vertical_scroll: usize,
+    pub selected_link_index: usize,
+    /// Heading of the section on screen, used instead of the scroll offset
+    /// when the article is wrapped to a different width than it was saved at
+    pub section: Option<String>,
+    /// Width of the article text when the view was saved
+    pub width: usize,
+}
+
+impl ArticleView {
+    /// A view of the start of `section`, wherever it ends up once wrapped
+    pub fn at_section(section: Option<String>) -> Self {
+        ArticleView {
+            section,
+            ..ArticleView::default()
+        }
+    }
+}
+
+/// An entry of the back/forward history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub title: String,
+    pub view: ArticleView,
+}
+
+impl HistoryPage {
+    pub fn new(title: String) -> Self {
+        HistoryPage {
+            title,
+            view: ArticleView::default(),
+        }
+    }
+}
+
 pub struct ArticleState {
     pub article_name: String,
+    /// Wiki the article comes from, which links in it are followed on
+    pub wiki: WikiSource,
     pub markdown_spans: Shared<Vec<FormattedSpan>>,
+    /// Bumped every time `markdown_spans` is replaced, so the layout knows to be redone
+    pub spans_generation: Shared<usize>,
     pub has_loaded_article: Shared<bool>,
     pub link_span_indices: Shared<Vec<usize>>,
     pub is_valid_page: Shared<bool>,
+    pub infobox: Shared<Vec<InfoboxEntry>>,
+    pub show_infobox: bool,
+    pub infobox_scroll: usize,
     pub selected_link_index: usize,
     pub vertical_scroll: usize,
-    back_history: VecDeque<String>,
-    forward_history: VecDeque<String>,
+    /// View to put back once the article being loaded is ready
+    pub pending_view: Option<ArticleView>,
+    /// Page requested but not yet loaded, logged in the history once it turns out to exist
+    pub pending_visit: Option<(String, WikiSource)>,
+    /// Whether scrolling moves the link selection onto the first link on screen
+    pub snap_links_to_scroll: bool,
+    /// Width and height of the article text as last drawn
+    pub viewport_size: Cell<(usize, usize)>,
+    /// Rows of infobox text and the height of its panel, as last drawn
+    pub infobox_size: Cell<(usize, usize)>,
+    /// Last layout made, with the width and spans generation it was made for
+    layout_cache: RefCell<Option<(usize, usize, Rc<ArticleLayout>)>>,
+    back_history: VecDeque<HistoryPage>,
+    forward_history: VecDeque<HistoryPage>,
 }
 
 impl ArticleState {
+    pub fn new(article_name: String) -> Self {
+        ArticleState {
+            article_name,
+            wiki: WikiSource::default(),
+            markdown_spans: create_shared(Vec::new()),
+            spans_generation: create_shared(0),
+            has_loaded_article: create_shared(false),
+            link_span_indices: create_shared(vec![]),
+            is_valid_page: create_shared(true),
+            infobox: create_shared(vec![]),
+            show_infobox: true,
+            infobox_scroll: 0,
+            selected_link_index: 0,
+            vertical_scroll: 0,
+            pending_view: None,
+            pending_visit: None,
+            snap_links_to_scroll: false,
+            viewport_size: Cell::new((0, 0)),
+            infobox_size: Cell::new((0, 0)),
+            layout_cache: RefCell::new(None),
+            back_history: VecDeque::new(),
+            forward_history: VecDeque::new(),
+        }
+    }
+
+    /// Whether the article has finished loading and has something to show
+    pub fn is_loaded(&self) -> bool {
+        let has_loaded = match self.has_loaded_article.try_lock() {
+            Ok(has_loaded) => *has_loaded,
+            Err(_) => false,
+        };
+        has_loaded
+            && match self.markdown_spans.try_lock() {
+                Ok(spans) => !spans.is_empty(),
+                Err(_) => false,
+            }
+    }
+
     pub fn scroll_link(&mut self, direction: ScrollDirection) {
         if let Ok(indices_results) = self.link_span_indices.try_lock() {
             let total_indices = (*indices_results).len();
@@ -348,83 +830,547 @@ impl ArticleState {
                     remainder(self.selected_link_index + increment, total_indices);
             }
         }
+        self.scroll_selected_link_into_view();
     }
 
-    pub fn scroll_vertically(&mut self, direction: ScrollDirection) {
-        match direction {
-            ScrollDirection::UP => self.vertical_scroll = self.vertical_scroll.saturating_sub(1),
-            ScrollDirection::DOWN => self.vertical_scroll = self.vertical_scroll.saturating_add(1),
+    fn selected_link_span_index(&self) -> Option<usize> {
+        match self.link_span_indices.try_lock() {
+            Ok(indices_results) => indices_results.get(self.selected_link_index).copied(),
+            Err(_) => None,
         }
     }
 
-    pub fn get_selected_link(&self) -> Option<String> {
-        if let Ok(indices_results) = self.link_span_indices.try_lock() {
-            if let Some(&index) = (*indices_results).get(self.selected_link_index) {
-                if let Ok(spans) = self.markdown_spans.try_lock() {
-                    if let Some(span) = (*spans).get(index) {
-                        return span.link.clone();
-                    }
-                }
+    /// Scrolls the least distance needed for the selected link's first line to be on screen
+    pub fn scroll_selected_link_into_view(&mut self) {
+        let viewport_height = self.viewport_size.get().1.max(1);
+        let link_line = self.selected_link_span_index().and_then(|span_index| {
+            self.layout()
+                .and_then(|layout| layout.line_of_span(span_index))
+        });
+        if let Some(line) = link_line {
+            if line < self.vertical_scroll {
+                self.set_vertical_scroll(line);
+            } else if line >= self.vertical_scroll + viewport_height {
+                self.set_vertical_scroll(line + 1 - viewport_height);
             }
         }
-        return None;
     }
 
-    pub fn go_back_a_page(&mut self) {
-        // take the last off back_history, put it at front of forward_history
-        if self.back_history.len() <= 1 {
-            return;
-        }
-        if let Some(title) = self.back_history.pop_back() {
-            self.forward_history.push_front(title);
+    /// Selects the first link on screen, leaving the selection alone if there isn't one
+    pub fn select_first_visible_link(&mut self) {
+        let layout = match self.layout() {
+            Some(layout) => layout,
+            None => return,
+        };
+        let visible_lines = self.vertical_scroll..self.vertical_scroll + self.viewport_size.get().1;
+        let first_visible_link = match self.link_span_indices.try_lock() {
+            Ok(indices_results) => indices_results.iter().position(|&span_index| {
+                layout
+                    .line_of_span(span_index)
+                    .is_some_and(|line| visible_lines.contains(&line))
+            }),
+            Err(_) => None,
+        };
+        if let Some(link_index) = first_visible_link {
+            self.selected_link_index = link_index;
         }
     }
 
-    pub fn go_forward_a_page(&mut self) {
-        // take the first off forward_history, put it at back of back_history
-        if let Some(title) = self.forward_history.pop_front() {
-            self.back_history.push_back(title);
+    pub fn toggle_snap_links_to_scroll(&mut self) {
+        self.snap_links_to_scroll = !self.snap_links_to_scroll;
+        if self.snap_links_to_scroll {
+            self.select_first_visible_link();
         }
     }
-}
 
-pub struct ThemeState {
-    pub themes: Vec<Theme>,
-    pub options: Vec<ActionItem>,
-    pub selected_index: usize,
-}
+    pub fn scroll_vertically(&mut self, direction: ScrollDirection) {
+        self.scroll_lines(direction, 1);
+    }
 
-impl ActionMenu for ThemeState {
-    fn total_options(&self) -> usize {
-        self.options.len()
+    pub fn scroll_page(&mut self, direction: ScrollDirection) {
+        // Keep one line of the previous page on screen for context
+        let page_height = self.viewport_size.get().1.saturating_sub(1).max(1);
+        self.scroll_lines(direction, page_height);
     }
 
-    fn set_index(&mut self, new_index: usize) -> () {
-        self.selected_index = new_index;
+    pub fn scroll_half_page(&mut self, direction: ScrollDirection) {
+        let half_page_height = (self.viewport_size.get().1 / 2).max(1);
+        self.scroll_lines(direction, half_page_height);
     }
 
-    fn get_index(&self) -> usize {
-        self.selected_index
+    pub fn scroll_to_top(&mut self) {
+        self.vertical_scroll = 0;
+        self.snap_link_selection();
     }
 
-    fn get_options(&self) -> &Vec<ActionItem> {
-        &self.options
+    pub fn scroll_to_bottom(&mut self) {
+        self.set_vertical_scroll(usize::MAX);
+        self.snap_link_selection();
     }
-}
 
-pub struct App {
-    pub title: TitleState,
-    pub search: SearchState,
-    pub search_menu: MenuState,
-    pub credit: CreditState,
-    pub article: ArticleState,
-    pub article_menu: MenuState,
-    pub theme_menu: ThemeState,
+    fn snap_link_selection(&mut self) {
+        if self.snap_links_to_scroll {
+            self.select_first_visible_link();
+        }
+    }
+
+    fn scroll_lines(&mut self, direction: ScrollDirection, amount: usize) {
+        match direction {
+            ScrollDirection::UP => {
+                self.set_vertical_scroll(self.vertical_scroll.saturating_sub(amount))
+            }
+            ScrollDirection::DOWN => {
+                self.set_vertical_scroll(self.vertical_scroll.saturating_add(amount))
+            }
+        }
+        self.snap_link_selection();
+    }
+
+    /// Scrolls to `line`, stopping once the end of the article reaches the bottom of the screen
+    pub fn set_vertical_scroll(&mut self, line: usize) {
+        // Waits out a page being loaded in, rather than dropping the scroll
+        let max_scroll = {
+            let spans = self.markdown_spans.lock().unwrap();
+            self.layout_of(&spans)
+                .max_scroll(self.viewport_size.get().1)
+        };
+        self.vertical_scroll = line.min(max_scroll);
+    }
+
+    /// Scrolls the infobox a row, stopping once its last row reaches the bottom of the panel
+    pub fn scroll_infobox(&mut self, direction: ScrollDirection) {
+        let (total_rows, panel_height) = self.infobox_size.get();
+        let max_scroll = total_rows.saturating_sub(panel_height);
+        self.infobox_scroll = match direction {
+            ScrollDirection::UP => self.infobox_scroll.saturating_sub(1),
+            ScrollDirection::DOWN => self.infobox_scroll.saturating_add(1),
+        }
+        .min(max_scroll);
+    }
+
+    pub fn toggle_infobox(&mut self) {
+        self.show_infobox = !self.show_infobox;
+    }
+
+    pub fn shared_article(&self) -> SharedArticle {
+        SharedArticle {
+            has_loaded_flag: shared_copy(&self.has_loaded_article),
+            markdown_spans: shared_copy(&self.markdown_spans),
+            spans_generation: shared_copy(&self.spans_generation),
+            link_indices: shared_copy(&self.link_span_indices),
+            infobox: shared_copy(&self.infobox),
+            is_valid_page: shared_copy(&self.is_valid_page),
+        }
+    }
+
+    /// The article wrapped to the width it was last drawn at
+    pub fn layout(&self) -> Option<Rc<ArticleLayout>> {
+        let spans = self.markdown_spans.try_lock().ok()?;
+        Some(self.layout_of(&spans))
+    }
+
+    /// `spans`, which must be the article's own, wrapped to the width it was last drawn at.
+    /// The layout is only redone when the width or the spans change.
+    pub fn layout_of(&self, spans: &[FormattedSpan]) -> Rc<ArticleLayout> {
+        let width = self.viewport_size.get().0;
+        let generation = match self.spans_generation.try_lock() {
+            Ok(generation) => *generation,
+            Err(_) => return Rc::new(ArticleLayout::new(spans, width)),
+        };
+        let mut layout_cache = self.layout_cache.borrow_mut();
+        match layout_cache.as_ref() {
+            Some((cached_width, cached_generation, layout))
+                if *cached_width == width && *cached_generation == generation =>
+            {
+                Rc::clone(layout)
+            }
+            _ => {
+                let layout = Rc::new(ArticleLayout::new(spans, width));
+                *layout_cache = Some((width, generation, Rc::clone(&layout)));
+                layout
+            }
+        }
+    }
+
+    /// Scrolls just enough to bring the given position of a span into view,
+    /// leaving it a third of the way down if it was off-screen
+    pub fn scroll_to_span_offset(&mut self, span_index: usize, offset: usize) {
+        let viewport_height = self.viewport_size.get().1;
+        if let Some(line) = self
+            .layout()
+            .and_then(|layout| layout.line_of_span_offset(span_index, offset))
+        {
+            if line < self.vertical_scroll || line >= self.vertical_scroll + viewport_height {
+                self.set_vertical_scroll(line.saturating_sub(viewport_height / 3));
+            }
+        }
+    }
+
+    /// Puts back `pending_view` once the article has loaded and been drawn
+    pub fn restore_pending_view(&mut self) {
+        let width = self.viewport_size.get().0;
+        if self.pending_view.is_none() || width == 0 {
+            return;
+        }
+        match self.has_loaded_article.try_lock() {
+            Ok(has_loaded) if *has_loaded => {}
+            _ => return,
+        }
+        let view = self.pending_view.take().unwrap_or_default();
+        let total_links = match self.link_span_indices.try_lock() {
+            Ok(indices_results) => indices_results.len(),
+            Err(_) => 0,
+        };
+        self.selected_link_index = view.selected_link_index.min(total_links.saturating_sub(1));
+
+        // The same scroll offset only shows the same text if the article wraps the same way
+        if view.width == width {
+            self.set_vertical_scroll(view.vertical_scroll);
+            return;
+        }
+        let section = match view.section {
+            Some(section) => section,
+            None => return,
+        };
+        let heading_index = match self.markdown_spans.try_lock() {
+            Ok(spans) => spans
+                .iter()
+                .find(|formatted_span| formatted_span.is_heading && formatted_span.text == section)
+                .map(|heading| heading.index),
+            Err(_) => None,
+        };
+        if let Some(span_index) = heading_index {
+            self.scroll_to_span(span_index);
+        }
+    }
+
+    pub fn current_view(&self) -> ArticleView {
+        ArticleView {
+            vertical_scroll: self.vertical_scroll,
+            selected_link_index: self.selected_link_index,
+            section: self.current_section(),
+            width: self.viewport_size.get().0,
+        }
+    }
+
+    /// Starts loading `title` from the article's wiki in place of the current article
+    pub fn load(&mut self, title: String, cache: Shared<CachingSession>) {
+        self.article_name = title.clone();
+        self.infobox_scroll = 0;
+        self.pending_visit = Some((title.clone(), self.wiki.clone()));
+        wikipedia::load_article_to_app(title, self.shared_article(), self.wiki.clone(), cache);
+    }
+
+    /// Goes to `title` on `wiki` as if following a link, adding it to the back/forward history
+    pub fn visit(&mut self, title: String, wiki: WikiSource, cache: Shared<CachingSession>) {
+        self.save_view_to_history();
+        self.selected_link_index = 0;
+        self.vertical_scroll = 0;
+        self.wiki = wiki;
+        self.load(title.clone(), cache);
+        self.forward_history.clear();
+        self.back_history.push_back(HistoryPage::new(title));
+    }
+
+    /// The page being read along with its back/forward history, for saving in the session
+    pub fn tab_session(&self) -> TabSession {
+        TabSession {
+            title: self.article_name.clone(),
+            wiki: Some(self.wiki.clone()),
+            // A view still waiting for the article to load is where the reader will be
+            view: self
+                .pending_view
+                .clone()
+                .unwrap_or_else(|| self.current_view()),
+            back_history: self.back_history.iter().cloned().collect(),
+            forward_history: self.forward_history.iter().cloned().collect(),
+        }
+    }
+
+    /// Remembers the current view with the page being read, ready for coming back to it
+    fn save_view_to_history(&mut self) {
+        let view = self.current_view();
+        if let Some(page) = self.back_history.back_mut() {
+            page.view = view;
+        }
+    }
+
+    /// The heading of the section at the top of the screen
+    pub fn current_section(&self) -> Option<String> {
+        let layout = self.layout()?;
+        let spans = self.markdown_spans.try_lock().ok()?;
+        layout
+            .heading_at_line(&spans, self.vertical_scroll)
+            .map(|heading| heading.text.clone())
+    }
+
+    pub fn scroll_to_span(&mut self, span_index: usize) {
+        if let Some(line) = self
+            .layout()
+            .and_then(|layout| layout.line_of_span(span_index))
+        {
+            self.set_vertical_scroll(line);
+        }
+    }
+
+    pub fn get_selected_link(&self) -> Option<String> {
+        if let Ok(indices_results) = self.link_span_indices.try_lock() {
+            if let Some(&index) = (*indices_results).get(self.selected_link_index) {
+                if let Ok(spans) = self.markdown_spans.try_lock() {
+                    if let Some(span) = (*spans).get(index) {
+                        return span.link.clone();
+                    }
+                }
+            }
+        }
+        return None;
+    }
+
+    /// Returns whether there was a page to go back to
+    pub fn go_back_a_page(&mut self) -> bool {
+        // take the last off back_history, put it at front of forward_history
+        if self.back_history.len() <= 1 {
+            return false;
+        }
+        self.save_view_to_history();
+        if let Some(page) = self.back_history.pop_back() {
+            self.forward_history.push_front(page);
+        }
+        true
+    }
+
+    /// Returns whether there was a page to go forward to
+    pub fn go_forward_a_page(&mut self) -> bool {
+        // take the first off forward_history, put it at back of back_history
+        if self.forward_history.is_empty() {
+            return false;
+        }
+        self.save_view_to_history();
+        if let Some(page) = self.forward_history.pop_front() {
+            self.back_history.push_back(page);
+        }
+        true
+    }
+}
+
+/// Two articles on screen at once, the tab's own article in the first pane
+pub struct SplitView {
+    /// The article in the pane without focus
+    pub other_article: ArticleState,
+    /// `Horizontal` for side by side panes, `Vertical` for one above the other
+    pub direction: Direction,
+    /// Whether the pane with focus is the left or top one
+    pub focus_first: bool,
+}
+
+/// A tab that isn't being shown, along with any split it was left in
+pub struct BackgroundTab {
+    /// The tab's own article, whichever pane had focus
+    pub article: ArticleState,
+    pub split: Option<SplitView>,
+}
+
+pub struct ThemeState {
+    pub themes: Vec<Theme>,
+    /// Problems with theme files that could not be read
+    pub load_errors: Vec<String>,
+    pub options: Vec<ActionItem>,
+    pub selected_index: usize,
+    /// Theme in use when the menu opened, put back if the menu closes without a choice
+    pub previous_theme: Option<Theme>,
+    pub previous_index: usize,
+}
+
+impl ThemeState {
+    fn theme_option(theme: &Theme) -> ActionItem {
+        ActionItem::new(&theme.name, move |app| {
+            app.choose_theme(app.theme_menu.selected_index)
+        })
+    }
+
+    /// Adds a theme to the menu, or replaces the one with the same name, returning its position
+    pub fn add_theme(&mut self, theme: Theme) -> usize {
+        match self
+            .themes
+            .iter()
+            .position(|existing| existing.name == theme.name)
+        {
+            Some(theme_index) => {
+                self.themes[theme_index] = theme;
+                theme_index
+            }
+            None => {
+                self.options.push(ThemeState::theme_option(&theme));
+                self.themes.push(theme);
+                self.themes.len() - 1
+            }
+        }
+    }
+}
+
+impl ActionMenu for ThemeState {
+    fn total_options(&self) -> usize {
+        self.options.len()
+    }
+
+    fn set_index(&mut self, new_index: usize) -> () {
+        self.selected_index = new_index;
+    }
+
+    fn get_index(&self) -> usize {
+        self.selected_index
+    }
+
+    fn get_options(&self) -> &Vec<ActionItem> {
+        &self.options
+    }
+}
+
+/// Rows of the theme editor: the theme's name, its base colours, then its style slots
+pub const THEME_EDITOR_FIELDS: [&str; 19] = [
+    "Name",
+    "Background",
+    "Text",
+    "Secondary",
+    "Tertiary",
+    "Highlight",
+    "Negative text",
+    "Heading 1",
+    "Heading 2",
+    "Heading 3",
+    "Heading 4",
+    "Heading 5",
+    "Heading 6",
+    "Link",
+    "Selected link",
+    "Visited link",
+    "Border",
+    "Scrollbar",
+    "Search match",
+];
+
+/// Row of `THEME_EDITOR_FIELDS` holding the first style slot
+pub const FIRST_STYLE_SLOT_FIELD: usize = 7;
+
+pub struct ThemeEditorState {
+    /// Copy of the theme being changed, shown on the whole screen while the editor is open
+    pub theme: Theme,
+    /// Row of `THEME_EDITOR_FIELDS` being changed
+    pub selected_index: usize,
+    pub input: String,
+    pub cursor_pos: usize,
+    /// Why the input can't be used, or why the theme couldn't be saved
+    pub message: Option<String>,
+}
+
+impl ThemeEditorState {
+    pub fn new(theme: Theme) -> Self {
+        let input = theme.name.clone();
+        ThemeEditorState {
+            theme,
+            selected_index: 0,
+            cursor_pos: input.len(),
+            input,
+            message: None,
+        }
+    }
+
+    pub fn field_value(&self, field_index: usize) -> String {
+        match field_index {
+            0 => self.theme.name.clone(),
+            _ if field_index < FIRST_STYLE_SLOT_FIELD => {
+                color_to_string(self.theme.base_colors()[field_index - 1])
+            }
+            _ => style_to_string(self.theme.style_slots()[field_index - FIRST_STYLE_SLOT_FIELD]),
+        }
+    }
+
+    pub fn select_field(&mut self, scroll_direction: ScrollDirection) {
+        let total_fields = THEME_EDITOR_FIELDS.len();
+        self.selected_index = match scroll_direction {
+            ScrollDirection::DOWN => remainder(self.selected_index + 1, total_fields),
+            ScrollDirection::UP => remainder(self.selected_index + total_fields - 1, total_fields),
+        };
+        self.input = self.field_value(self.selected_index);
+        self.cursor_pos = self.input.len();
+        self.message = None;
+    }
+
+    /// Puts the input into the selected field as it is typed, whenever it can be read
+    pub fn apply_input(&mut self) {
+        self.message = None;
+        if self.selected_index == 0 {
+            self.theme.name = self.input.trim().to_string();
+            return;
+        }
+        if self.selected_index >= FIRST_STYLE_SLOT_FIELD {
+            let slot_index = self.selected_index - FIRST_STYLE_SLOT_FIELD;
+            match parse_style(&self.input) {
+                Ok(style) => *self.theme.style_slots_mut()[slot_index] = style,
+                Err(e) => self.message = Some(e),
+            }
+            return;
+        }
+        match parse_color_input(&self.input) {
+            Ok(color) => {
+                let mut colors = self.theme.base_colors();
+                colors[self.selected_index - 1] = color;
+                self.theme = self.theme.with_colors(colors);
+            }
+            Err(e) => self.message = Some(e),
+        }
+    }
+}
+
+impl TypeableState for ThemeEditorState {
+    fn get_input(&self) -> String {
+        self.input.clone()
+    }
+
+    fn input_mut(&mut self) -> &mut String {
+        &mut self.input
+    }
+
+    fn get_cursor_pos(&self) -> usize {
+        self.cursor_pos
+    }
+
+    fn set_cursor_pos(&mut self, new_cursor_pos: usize) {
+        self.cursor_pos = new_cursor_pos;
+    }
+}
+
+pub struct App {
+    pub title: TitleState,
+    pub search: SearchState,
+    pub search_menu: MenuState,
+    pub credit: CreditState,
+    /// The article with focus in the tab being shown
+    pub article: ArticleState,
+    /// The other tabs, in tab order with the one being shown left out
+    pub tabs: Vec<BackgroundTab>,
+    /// Position of the tab being shown among the tabs
+    pub active_tab: usize,
+    /// Second pane of the tab being shown
+    pub split: Option<SplitView>,
+    pub article_search: ArticleSearchState,
+    pub link_hints: LinkHintState,
+    pub article_menu: MenuState,
+    pub contents_menu: MenuState,
+    pub theme_menu: ThemeState,
+    pub theme_editor: ThemeEditorState,
+    pub command_palette: CommandPaletteState,
+    pub bookmarks: BookmarksState,
+    pub history: HistoryState,
     pub cache: Shared<CachingSession>,
     pub is_running: bool,
     pub state: AppState,
     pub theme: Theme,
+    /// What the terminal can show, worked out once at startup
+    pub color_support: ColorSupport,
+    pub keymap: Keymap,
     pub config: Args,
+    pub wiki: WikiSource,
     pub debug_text: String,
 }
 
@@ -452,37 +1398,77 @@ impl Default for App {
                 selected_index: 0,
                 options: vec![],
             },
-            article: ArticleState {
-                article_name: String::from("Philosophy"),
-                markdown_spans: create_shared(Vec::new()),
-                has_loaded_article: create_shared(false),
-                link_span_indices: create_shared(vec![]),
-                is_valid_page: create_shared(true),
-                selected_link_index: 0,
-                vertical_scroll: 0,
-                back_history: VecDeque::new(),
-                forward_history: VecDeque::new(),
+            article: ArticleState::new(String::from("Philosophy")),
+            tabs: vec![],
+            active_tab: 0,
+            split: None,
+            article_search: ArticleSearchState {
+                input: String::new(),
+                cursor_pos: 0,
+                matches: vec![],
+                current_match: 0,
+            },
+            link_hints: LinkHintState {
+                hints: vec![],
+                typed: String::new(),
             },
             article_menu: MenuState {
                 selected_index: 0,
                 options: vec![],
             },
+            contents_menu: MenuState {
+                selected_index: 0,
+                options: vec![],
+            },
             theme_menu: ThemeState {
                 themes: vec![],
+                load_errors: vec![],
                 selected_index: 0,
                 options: vec![],
+                previous_theme: None,
+                previous_index: 0,
+            },
+            theme_editor: ThemeEditorState::new(Theme::default()),
+            command_palette: CommandPaletteState {
+                input: String::new(),
+                cursor_pos: 0,
+                items: vec![],
+                filtered_items: vec![],
+                selected_index: 0,
+                return_state: AppState::Title,
+            },
+            bookmarks: BookmarksState {
+                store: BookmarkStore::load(),
+                filter: String::new(),
+                cursor_pos: 0,
+                selected_index: 0,
+                editing_tags: None,
+                tags_input: String::new(),
+                return_state: AppState::Search,
+            },
+            history: HistoryState {
+                log: HistoryLog::load(),
+                filter: String::new(),
+                cursor_pos: 0,
+                selected_index: 0,
+                return_state: AppState::Search,
             },
             cache: create_shared(CachingSession::new()),
             is_running: false,
             state: AppState::Title,
             theme: Theme::default(),
+            color_support: ColorSupport::TrueColor,
+            keymap: Keymap::default(),
             config: Args::default(),
+            wiki: WikiSource::default(),
             debug_text: String::from(""),
         };
 
         app.search_menu.options = vec![
             ActionItem::new("Resume", |app| app.state = AppState::Search),
-            ActionItem::new("Themes", |app| app.state = AppState::ThemeMenu),
+            ActionItem::new("Bookmarks", |app| app.open_bookmarks()),
+            ActionItem::new("History", |app| app.open_history()),
+            ActionItem::new("Themes", |app| app.open_theme_menu()),
             ActionItem::new("Credits", |app| app.state = AppState::Credit),
             ActionItem::new("Quit", |app| app.is_running = false),
         ];
@@ -490,8 +1476,43 @@ impl Default for App {
         app.article_menu.options = vec![
             ActionItem::new("Resume", |app| app.state = AppState::Article),
             ActionItem::new("Search", |app| app.state = AppState::Search),
+            ActionItem::new("Contents", |app| app.open_contents()),
+            ActionItem::new("Add bookmark", |app| {
+                app.add_bookmark();
+                app.state = AppState::Article;
+            }),
+            ActionItem::new("Bookmarks", |app| app.open_bookmarks()),
+            ActionItem::new("History", |app| app.open_history()),
+            ActionItem::new("Open link in new tab", |app| {
+                app.open_selected_link_in_new_tab();
+                app.state = AppState::Article;
+            }),
+            ActionItem::new("Close tab", |app| {
+                app.state = AppState::Article;
+                app.close_tab();
+            }),
+            ActionItem::new("Split side by side", |app| {
+                app.toggle_split(Direction::Horizontal);
+                app.state = AppState::Article;
+            }),
+            ActionItem::new("Split one above the other", |app| {
+                app.toggle_split(Direction::Vertical);
+                app.state = AppState::Article;
+            }),
+            ActionItem::new("Follow link in other pane", |app| {
+                app.follow_link_in_other_pane();
+                app.state = AppState::Article;
+            }),
             ActionItem::new("← Go back", |app| app.go_to_previous_article()),
             ActionItem::new("Go forward →", |app| app.go_to_next_article()),
+            ActionItem::new("Toggle infobox", |app| {
+                app.article.toggle_infobox();
+                app.state = AppState::Article;
+            }),
+            ActionItem::new("Toggle link snapping", |app| {
+                app.article.toggle_snap_links_to_scroll();
+                app.state = AppState::Article;
+            }),
             ActionItem::new("Quit", |app| app.is_running = false),
         ];
 
@@ -502,42 +1523,19 @@ impl Default for App {
             ActionItem::new("Back to menu", |app| app.state = AppState::SearchMenu),
         ];
 
-        let theme_file_result = File::options().read(true).write(false).open("./themes.txt");
-        if let Ok(theme_file) = theme_file_result {
-            let reader = BufReader::new(theme_file);
-            for line_result in reader.lines() {
-                if let Ok(line) = line_result {
-                    let line_split: Vec<&str> = line.split(' ').collect();
-                    let maybe_theme_name = line_split.get(0);
-                    let maybe_theme_colours = line_split.get(1);
-                    // if let Some(&theme_colours) = maybe_theme_colours {
-                    if maybe_theme_colours.is_none() {
-                        break;
-                    }
-                    if maybe_theme_name.is_none() {
-                        break;
-                    }
-                    app.theme_menu.themes.push(Theme::from_hex_string_series(
-                        String::from(maybe_theme_name.unwrap().to_owned()),
-                        String::from(maybe_theme_colours.unwrap().to_owned()),
-                    ));
-                    // }
-                }
-            }
-        }
+        (app.theme_menu.themes, app.theme_menu.load_errors) = load_themes();
         if app.theme_menu.themes.len() == 0 {
             app.theme_menu.themes.push(Theme::from_hex_string_series(
                 "Normal".to_string(),
                 "2a3138-ffffff-c19c00-13a10e-3b78ff-000000".to_string(),
             ));
         }
-        for theme in app.theme_menu.themes.iter() {
-            app.theme_menu
-                .options
-                .push(ActionItem::new(&theme.name, move |app| {
-                    app.theme = app.theme_menu.themes[app.theme_menu.selected_index].clone()
-                }));
-        }
+        app.theme_menu.options = app
+            .theme_menu
+            .themes
+            .iter()
+            .map(ThemeState::theme_option)
+            .collect();
 
         app
     }
@@ -562,6 +1560,7 @@ impl App {
                     input,
                     loading_flag,
                     app_results,
+                    self.wiki.clone(),
                     caching_session,
                 );
             }
@@ -577,6 +1576,7 @@ impl App {
 
     pub fn try_getting_page(&mut self, title: String) {
         // load the page, if a Page is found, return Ok, else Err
+        self.article.wiki = self.wiki.clone();
         self.set_article_page(title.clone());
 
         let is_valid_page: bool;
@@ -602,59 +1602,787 @@ impl App {
     pub fn set_article_page(&mut self, title: String) {
         // *self.article.has_loaded_article.lock().unwrap() = false;
 
-        self.article.article_name = title.clone();
-        let markdown_spans = shared_copy(&self.article.markdown_spans);
-        let has_loaded_flag = shared_copy(&self.article.has_loaded_article);
+        self.article_search.clear();
         let cache = shared_copy(&self.cache);
-        let link_indices = shared_copy(&self.article.link_span_indices);
-        let is_valid_page = shared_copy(&self.article.is_valid_page);
-        wikipedia::load_article_to_app(
-            title.clone(),
-            has_loaded_flag,
-            markdown_spans,
-            link_indices,
-            is_valid_page,
-            cache,
-        );
+        self.article.load(title, cache);
     }
 
     pub fn view_selected_article_from_search(&mut self) {
         if let Some(title) = self.search.selected_search_result_title() {
-            self.state = AppState::Article;
-            self.set_article_page(title.clone());
-            self.article.back_history.clear();
-            self.article.forward_history.clear();
-            self.article.back_history.push_back(title.clone());
-            // self.article.history.push_back(title.clone());
+            self.open_article(title, self.wiki.clone());
         } else {
             self.state = AppState::SearchMenu;
         }
     }
+
+    /// Shows an article from `wiki` as the start of a fresh back/forward history
+    pub fn open_article(&mut self, title: String, wiki: WikiSource) {
+        self.state = AppState::Article;
+        self.article.wiki = wiki;
+        self.article.selected_link_index = 0;
+        self.article.vertical_scroll = 0;
+        self.set_article_page(title.clone());
+        self.article.back_history.clear();
+        self.article.forward_history.clear();
+        self.article
+            .back_history
+            .push_back(HistoryPage::new(title.clone()));
+        // self.article.history.push_back(title.clone());
+    }
+
+    /// Logs the pages last asked for in each tab in the history once they have loaded
+    pub fn record_pending_visits(&mut self) {
+        let split_article = self.split.as_mut().map(|split| &mut split.other_article);
+        let background_articles = self.tabs.iter_mut().flat_map(|tab| {
+            let split_article = tab.split.as_mut().map(|split| &mut split.other_article);
+            std::iter::once(&mut tab.article).chain(split_article)
+        });
+        for article in std::iter::once(&mut self.article)
+            .chain(split_article)
+            .chain(background_articles)
+        {
+            if article.pending_visit.is_none() {
+                continue;
+            }
+            match article.has_loaded_article.try_lock() {
+                Ok(has_loaded) if *has_loaded => {}
+                _ => continue,
+            }
+            if let Some((title, wiki)) = article.pending_visit.take() {
+                if *article.is_valid_page.lock().unwrap() {
+                    self.history.log.record(title, wiki).unwrap_or(());
+                }
+            }
+        }
+    }
+
+    pub fn total_tabs(&self) -> usize {
+        self.tabs.len() + 1
+    }
+
+    /// The own article of the tab being shown, which is in the first pane when split
+    pub fn own_article(&self) -> &ArticleState {
+        match &self.split {
+            Some(split) if !split.focus_first => &split.other_article,
+            _ => &self.article,
+        }
+    }
+
+    /// Names of the articles in every tab, in tab order
+    pub fn tab_titles(&self) -> Vec<String> {
+        let mut titles: Vec<String> = self
+            .tabs
+            .iter()
+            .map(|tab| tab.article.article_name.clone())
+            .collect();
+        titles.insert(self.active_tab, self.own_article().article_name.clone());
+        titles
+    }
+
+    /// Starts loading `title` from `wiki` into a tab of its own, without showing it
+    /// or logging it in the history
+    fn tab_article(&self, title: String, wiki: WikiSource) -> ArticleState {
+        let mut article = ArticleState::new(title.clone());
+        article.wiki = wiki.clone();
+        article.snap_links_to_scroll = self.article.snap_links_to_scroll;
+        article.viewport_size.set(self.article.viewport_size.get());
+        article
+            .back_history
+            .push_back(HistoryPage::new(title.clone()));
+        wikipedia::load_article_to_app(
+            title,
+            article.shared_article(),
+            wiki,
+            shared_copy(&self.cache),
+        );
+        article
+    }
+
+    /// Starts loading `title` from `wiki` into a tab of its own, without showing it
+    fn new_tab(&self, title: String, wiki: WikiSource) -> ArticleState {
+        let mut article = self.tab_article(title.clone(), wiki.clone());
+        article.pending_visit = Some((title, wiki));
+        article
+    }
+
+    /// Opens `title` in a tab just after the current one, staying on the current tab
+    pub fn open_in_new_tab(&mut self, title: String, wiki: WikiSource) {
+        let article = self.new_tab(title, wiki);
+        self.tabs.insert(
+            self.active_tab,
+            BackgroundTab {
+                article,
+                split: None,
+            },
+        );
+    }
+
+    pub fn open_selected_link_in_new_tab(&mut self) {
+        if let Some(link) = self.article.get_selected_link() {
+            self.open_in_new_tab(link_target_title(&link), self.article.wiki.clone());
+        }
+    }
+
+    pub fn switch_to_tab(&mut self, tab_index: usize) {
+        if tab_index == self.active_tab || tab_index >= self.total_tabs() {
+            return;
+        }
+        let previous_tab = self.put_away_tab();
+        self.tabs.insert(self.active_tab, previous_tab);
+        let tab = self.tabs.remove(tab_index);
+        self.show_tab(tab);
+        self.active_tab = tab_index;
+    }
+
+    /// Takes the tab being shown, with its split, out of the way of another
+    fn put_away_tab(&mut self) -> BackgroundTab {
+        let mut split = self.split.take();
+        if let Some(split) = split.as_mut().filter(|split| !split.focus_first) {
+            std::mem::swap(&mut self.article, &mut split.other_article);
+        }
+        BackgroundTab {
+            article: std::mem::replace(&mut self.article, ArticleState::new(String::new())),
+            split,
+        }
+    }
+
+    /// Shows `tab` with its split, focused on the pane it was left on
+    fn show_tab(&mut self, tab: BackgroundTab) {
+        self.article = tab.article;
+        self.split = tab.split;
+        if let Some(split) = self.split.as_mut().filter(|split| !split.focus_first) {
+            std::mem::swap(&mut self.article, &mut split.other_article);
+        }
+        self.article_search.clear();
+    }
+
+    pub fn cycle_tab(&mut self, direction: ScrollDirection) {
+        let total_tabs = self.total_tabs();
+        let tab_index = match direction {
+            ScrollDirection::UP => remainder(self.active_tab + total_tabs - 1, total_tabs),
+            ScrollDirection::DOWN => remainder(self.active_tab + 1, total_tabs),
+        };
+        self.switch_to_tab(tab_index);
+    }
+
+    /// Closes the current tab for the one after it, or goes back to searching if it was the last
+    pub fn close_tab(&mut self) {
+        self.article_search.clear();
+        self.split = None;
+        if self.tabs.is_empty() {
+            let snap_links_to_scroll = self.article.snap_links_to_scroll;
+            self.article = ArticleState::new(String::new());
+            self.article.snap_links_to_scroll = snap_links_to_scroll;
+            self.state = AppState::Search;
+            return;
+        }
+        if self.active_tab == self.tabs.len() {
+            self.active_tab -= 1;
+        }
+        let tab = self.tabs.remove(self.active_tab);
+        self.show_tab(tab);
+    }
+
+    /// The open tabs, for picking up where this session left off next time
+    pub fn session(&self) -> Session {
+        let mut tabs: Vec<TabSession> = self
+            .tabs
+            .iter()
+            .map(|tab| tab.article.tab_session())
+            .collect();
+        let mut active_tab = self.active_tab;
+        // An article that is still loading is saved too, so quitting early doesn't lose its tab
+        if !self.own_article().article_name.is_empty() {
+            tabs.insert(active_tab, self.own_article().tab_session());
+        } else {
+            active_tab = active_tab.min(tabs.len().saturating_sub(1));
+        }
+        let search = match self.search.results.try_lock() {
+            Ok(results) if !self.search.current_query.is_empty() => Some(SearchSession {
+                query: self.search.current_query.clone(),
+                results: results.clone(),
+                selected_index: self.search.selected_index,
+            }),
+            _ => None,
+        };
+        Session {
+            state: Some(self.state),
+            wiki: Some(self.wiki.clone()),
+            tabs,
+            active_tab,
+            theme: Some(self.theme.name.clone()),
+            search,
+        }
+    }
+
+    /// Picks up where a saved session left off: its tabs, each where it was left,
+    /// the search and its results, the theme and the screen that was open
+    pub fn restore_session(&mut self, session: Session) {
+        if let Some(wiki) = session.wiki {
+            self.wiki = wiki;
+        }
+
+        if let Some(theme_name) = session.theme {
+            self.apply_theme_named(&theme_name);
+        }
+
+        if let Some(search) = session.search {
+            self.search.input = search.query.clone();
+            self.search.cursor_pos = search.query.len();
+            self.search.current_query = search.query;
+            self.search.selected_index = search.selected_index;
+            self.search.text_box_is_highlighted = search.results.is_empty();
+            *self.search.results.lock().unwrap() = search.results;
+        }
+
+        let has_tabs = !session.tabs.is_empty();
+        if has_tabs {
+            let active_tab = session.active_tab.min(session.tabs.len() - 1);
+            let mut tabs: Vec<BackgroundTab> = session
+                .tabs
+                .into_iter()
+                .map(|tab| {
+                    let wiki = tab.wiki.unwrap_or_else(|| self.wiki.clone());
+                    let mut article = self.tab_article(tab.title, wiki);
+                    article.pending_view = Some(tab.view);
+                    if !tab.back_history.is_empty() {
+                        article.back_history = tab.back_history.into();
+                    }
+                    article.forward_history = tab.forward_history.into();
+                    BackgroundTab {
+                        article,
+                        split: None,
+                    }
+                })
+                .collect();
+            self.split = None;
+            let tab = tabs.remove(active_tab);
+            self.show_tab(tab);
+            self.tabs = tabs;
+            self.active_tab = active_tab;
+        }
+
+        // Menus and overlays are left for the screen they were opened over
+        let base_state = match has_tabs {
+            true => AppState::Article,
+            false => AppState::Search,
+        };
+        self.state = match session.state.unwrap_or(base_state) {
+            AppState::Article
+            | AppState::ArticleSearch
+            | AppState::LinkHints
+            | AppState::ArticleMenu
+            | AppState::Contents => base_state,
+            AppState::Bookmarks => {
+                self.bookmarks.return_state = base_state;
+                AppState::Bookmarks
+            }
+            AppState::History => {
+                self.history.return_state = base_state;
+                AppState::History
+            }
+            _ => AppState::Search,
+        };
+    }
+
+    pub fn set_color_support(&mut self, color_support: ColorSupport) {
+        self.color_support = color_support;
+        self.theme = self.theme.for_color_support(color_support);
+    }
+
+    fn apply_theme(&mut self, theme_index: usize) {
+        if let Some(theme) = self.theme_menu.themes.get(theme_index) {
+            self.theme = theme.for_color_support(self.color_support);
+            self.theme_menu.selected_index = theme_index;
+        }
+    }
+
+    /// Switches to the theme called `theme_name`, returning whether there is one
+    pub fn apply_theme_named(&mut self, theme_name: &str) -> bool {
+        match self
+            .theme_menu
+            .themes
+            .iter()
+            .position(|theme| theme.name == theme_name)
+        {
+            Some(theme_index) => {
+                self.apply_theme(theme_index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Switches theme and keeps the choice in the config file for next time
+    pub fn choose_theme(&mut self, theme_index: usize) {
+        self.apply_theme(theme_index);
+        self.theme_menu.previous_theme = Some(self.theme.clone());
+        self.theme_menu.previous_index = theme_index;
+        self.config.theme = Some(self.theme.name.clone());
+        save_theme_to_config(&self.theme.name).unwrap_or(());
+    }
+
+    pub fn open_theme_menu(&mut self) {
+        self.theme_menu.previous_theme = Some(self.theme.clone());
+        self.theme_menu.previous_index = self.theme_menu.selected_index;
+        self.state = AppState::ThemeMenu;
+    }
+
+    /// Shows the highlighted theme without choosing it yet
+    pub fn preview_selected_theme(&mut self) {
+        self.apply_theme(self.theme_menu.selected_index);
+    }
+
+    /// Leaves the theme menu, putting back the theme in use before it opened
+    pub fn close_theme_menu(&mut self) {
+        if let Some(previous_theme) = self.theme_menu.previous_theme.take() {
+            self.theme = previous_theme;
+            self.theme_menu.selected_index = self.theme_menu.previous_index;
+        }
+        self.state = AppState::Search;
+    }
+
+    /// Opens the editor on a copy of the highlighted theme
+    pub fn open_theme_editor(&mut self) {
+        if let Some(theme) = self.theme_menu.themes.get(self.theme_menu.selected_index) {
+            self.theme_editor = ThemeEditorState::new(theme.clone());
+            self.preview_selected_theme();
+            self.state = AppState::ThemeEditor;
+        }
+    }
+
+    /// Leaves the editor without saving, going back to the highlighted theme
+    pub fn close_theme_editor(&mut self) {
+        self.preview_selected_theme();
+        self.state = AppState::ThemeMenu;
+    }
+
+    pub fn apply_theme_editor_input(&mut self) {
+        self.theme_editor.apply_input();
+        self.theme = self
+            .theme_editor
+            .theme
+            .for_color_support(self.color_support);
+    }
+
+    /// Saves the theme being edited to the user's theme directory and switches to it
+    pub fn save_edited_theme(&mut self) {
+        let theme = self.theme_editor.theme.clone();
+        if theme.name.is_empty() {
+            self.theme_editor.message = Some(String::from("The theme needs a name"));
+            return;
+        }
+        match theme.save() {
+            Ok(_) => {
+                let theme_index = self.theme_menu.add_theme(theme);
+                self.choose_theme(theme_index);
+                self.state = AppState::ThemeMenu;
+            }
+            Err(e) => {
+                self.theme_editor.message = Some(format!("Could not save the theme: {}", e));
+            }
+        }
+    }
+
+    pub fn open_history(&mut self) {
+        if self.state != AppState::History {
+            self.history.return_state = match self.state {
+                AppState::ArticleMenu => AppState::Article,
+                AppState::Title | AppState::SearchMenu => AppState::Search,
+                other_state => other_state,
+            };
+        }
+        self.history.selected_index = 0;
+        self.state = AppState::History;
+    }
+
+    pub fn open_selected_history_entry(&mut self) {
+        let entry = match self.history.selected_entry_index() {
+            Some(entry_index) => self.history.log.entries[entry_index].clone(),
+            None => return,
+        };
+        self.open_article(entry.title, entry.wiki);
+    }
+
+    /// Bookmarks the article being read, at the section on screen
+    pub fn add_bookmark(&mut self) {
+        if !self.has_article() {
+            return;
+        }
+        let bookmark = Bookmark::new(
+            self.article.article_name.clone(),
+            self.article.wiki.clone(),
+            self.article.current_section(),
+        );
+        self.bookmarks.store.add(bookmark).unwrap_or(());
+    }
+
+    pub fn open_bookmarks(&mut self) {
+        if self.state != AppState::Bookmarks {
+            self.bookmarks.return_state = match self.state {
+                AppState::ArticleMenu => AppState::Article,
+                AppState::Title | AppState::SearchMenu => AppState::Search,
+                other_state => other_state,
+            };
+        }
+        self.bookmarks.finish_editing_tags(false);
+        self.bookmarks.selected_index = 0;
+        self.state = AppState::Bookmarks;
+    }
+
+    pub fn open_selected_bookmark(&mut self) {
+        let bookmark = match self.bookmarks.selected_bookmark_index() {
+            Some(bookmark_index) => self.bookmarks.store.bookmarks[bookmark_index].clone(),
+            None => return,
+        };
+        self.open_article(bookmark.title, bookmark.wiki);
+        self.article.pending_view = Some(ArticleView::at_section(bookmark.section));
+    }
     pub fn view_selected_article_from_selected_link(&mut self) {
         if let Some(title) = self.article.get_selected_link() {
-            self.article.selected_link_index = 0;
-            self.article.vertical_scroll = 0;
-            let formatted_title = title.replace("_", " ").replace("./", "");
-            self.set_article_page(formatted_title.clone());
-            self.article.forward_history.clear();
-            self.article.back_history.push_back(formatted_title.clone());
+            self.article_search.clear();
+            let cache = shared_copy(&self.cache);
+            let wiki = self.article.wiki.clone();
+            self.article.visit(link_target_title(&title), wiki, cache);
             // self.article.history.push_back(formatted_title.clone());
         }
     }
 
+    /// Splits the screen in two along `direction`, or closes the split if it is already that way.
+    /// The new pane starts on the article being read.
+    pub fn toggle_split(&mut self, direction: Direction) {
+        match &mut self.split {
+            Some(split) if split.direction == direction => self.close_split(),
+            Some(split) => split.direction = direction,
+            None => {
+                // The same article is already in history, so opening it again is not a new visit
+                let other_article =
+                    self.tab_article(self.article.article_name.clone(), self.article.wiki.clone());
+                self.split = Some(SplitView {
+                    other_article,
+                    direction,
+                    focus_first: true,
+                });
+            }
+        }
+    }
+
+    /// Goes from no split, to side by side, to one above the other, and back
+    pub fn cycle_split(&mut self) {
+        match self.split.as_ref().map(|split| split.direction) {
+            None => self.toggle_split(Direction::Horizontal),
+            Some(Direction::Horizontal) => self.toggle_split(Direction::Vertical),
+            Some(Direction::Vertical) => self.close_split(),
+        }
+    }
+
+    /// Closes the second pane, leaving the tab's own article
+    pub fn close_split(&mut self) {
+        if let Some(mut split) = self.split.take() {
+            if !split.focus_first {
+                std::mem::swap(&mut self.article, &mut split.other_article);
+            }
+            self.article_search.clear();
+        }
+    }
+
+    pub fn switch_pane(&mut self) {
+        if let Some(split) = &mut self.split {
+            std::mem::swap(&mut self.article, &mut split.other_article);
+            split.focus_first = !split.focus_first;
+            self.article_search.clear();
+        }
+    }
+
+    /// Opens the selected link in the pane without focus, splitting the screen if needed
+    pub fn follow_link_in_other_pane(&mut self) {
+        let title = match self.article.get_selected_link() {
+            Some(link) => link_target_title(&link),
+            None => return,
+        };
+        match &mut self.split {
+            Some(split) => {
+                let cache = shared_copy(&self.cache);
+                split
+                    .other_article
+                    .visit(title, self.article.wiki.clone(), cache);
+            }
+            None => {
+                let other_article = self.new_tab(title, self.article.wiki.clone());
+                self.split = Some(SplitView {
+                    other_article,
+                    direction: Direction::Horizontal,
+                    focus_first: true,
+                });
+            }
+        }
+    }
+
+    /// Puts back the view of any pane whose article has just loaded
+    pub fn restore_pending_views(&mut self) {
+        self.article.restore_pending_view();
+        if let Some(split) = &mut self.split {
+            split.other_article.restore_pending_view();
+        }
+    }
+
+    /// Lists the article's headings, indented by level, each jumping to its section
+    pub fn open_contents(&mut self) {
+        let (headings, current_heading_index) = match self.article.markdown_spans.try_lock() {
+            Ok(spans) => {
+                let headings: Vec<FormattedSpan> = spans
+                    .iter()
+                    .filter(|formatted_span| formatted_span.is_heading)
+                    .cloned()
+                    .collect();
+                let current_heading_index = self.article.layout().and_then(|layout| {
+                    layout
+                        .heading_at_line(&spans, self.article.vertical_scroll)
+                        .map(|heading| heading.index)
+                });
+                (headings, current_heading_index)
+            }
+            Err(_) => return,
+        };
+        if headings.is_empty() {
+            self.state = AppState::Article;
+            return;
+        }
+
+        let top_level = headings
+            .iter()
+            .map(|heading| heading.heading_level)
+            .min()
+            .unwrap_or(1);
+        self.contents_menu.options = headings
+            .iter()
+            .map(|heading| {
+                let span_index = heading.index;
+                let indent = "  ".repeat(heading.heading_level - top_level);
+                ActionItem::new(&format!("{indent}{}", heading.text), move |app| {
+                    app.article.scroll_to_span(span_index);
+                    app.state = AppState::Article;
+                })
+            })
+            .collect();
+        self.contents_menu.selected_index = headings
+            .iter()
+            .position(|heading| Some(heading.index) == current_heading_index)
+            .unwrap_or(0);
+        self.state = AppState::Contents;
+    }
+
+    /// Whether an article has finished loading, whichever screen is showing
+    pub fn has_article(&self) -> bool {
+        self.article.is_loaded()
+    }
+
+    /// Every action that can be run from where the app is now, for the command palette
+    fn command_palette_items(&self) -> Vec<ActionItem> {
+        let labelled = |prefix: &str, items: &Vec<ActionItem>| -> Vec<ActionItem> {
+            items
+                .iter()
+                .map(|item| ActionItem {
+                    label: format!("{prefix}: {}", item.label()),
+                    action: item.action_clone(),
+                })
+                .collect()
+        };
+
+        let mut items = labelled("Menu", &self.search_menu.options);
+        items.extend(labelled("Credits", &self.credit.options));
+        items.extend(
+            self.theme_menu
+                .themes
+                .iter()
+                .enumerate()
+                .map(|(theme_index, theme)| {
+                    ActionItem::new(&format!("Switch theme: {}", theme.name), move |app| {
+                        app.choose_theme(theme_index)
+                    })
+                }),
+        );
+
+        if !self.has_article() {
+            return items;
+        }
+        items.extend(labelled("Article", &self.article_menu.options));
+        if !self.tabs.is_empty() {
+            items.extend(
+                self.tab_titles()
+                    .iter()
+                    .enumerate()
+                    .map(|(tab_index, title)| {
+                        ActionItem::new(
+                            &format!("Switch tab: {} {}", tab_index + 1, title),
+                            move |app| {
+                                app.state = AppState::Article;
+                                app.switch_to_tab(tab_index);
+                            },
+                        )
+                    }),
+            );
+        }
+        if let (Ok(spans), Ok(link_span_indices)) = (
+            self.article.markdown_spans.try_lock(),
+            self.article.link_span_indices.try_lock(),
+        ) {
+            items.extend(
+                spans
+                    .iter()
+                    .filter(|formatted_span| formatted_span.is_heading)
+                    .map(|heading| {
+                        let span_index = heading.index;
+                        ActionItem::new(&format!("Go to section: {}", heading.text), move |app| {
+                            app.state = AppState::Article;
+                            app.article.scroll_to_span(span_index);
+                        })
+                    }),
+            );
+            items.extend(link_span_indices.iter().enumerate().filter_map(
+                |(link_index, span_index)| {
+                    let link_span = spans.get(*span_index)?;
+                    let link_text = link_span.text.trim();
+                    let link_title = link_span
+                        .link
+                        .as_deref()
+                        .map(link_target_title)
+                        .unwrap_or_default();
+                    let label = match link_title.eq_ignore_ascii_case(link_text) {
+                        true => format!("Open link: {link_text}"),
+                        false => format!("Open link: {link_text} → {link_title}"),
+                    };
+                    Some(ActionItem::new(&label, move |app| {
+                        app.state = AppState::Article;
+                        app.article.selected_link_index = link_index;
+                        app.view_selected_article_from_selected_link();
+                    }))
+                },
+            ));
+        }
+        items
+    }
+
+    pub fn open_command_palette(&mut self) {
+        if self.state == AppState::CommandPalette {
+            return;
+        }
+        self.command_palette.items = self.command_palette_items();
+        self.command_palette.input.clear();
+        self.command_palette.cursor_pos = 0;
+        self.command_palette.filter_items();
+        self.command_palette.return_state = self.state;
+        self.state = AppState::CommandPalette;
+    }
+
+    pub fn run_command_palette_selection(&mut self) {
+        self.state = self.command_palette.return_state;
+        if self.command_palette.total_options() > 0 {
+            self.command_palette.get_selected_action()(self);
+        }
+    }
+
+    /// Labels every link on screen, ready for one to be picked by typing its label
+    pub fn open_link_hints(&mut self) {
+        let layout = match self.article.layout() {
+            Some(layout) => layout,
+            None => return,
+        };
+        let visible_lines = self.article.vertical_scroll
+            ..self.article.vertical_scroll + self.article.viewport_size.get().1;
+        let visible_links: Vec<(usize, usize)> = match self.article.link_span_indices.try_lock() {
+            Ok(indices_results) => indices_results
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|(_, span_index)| {
+                    layout
+                        .line_of_span(*span_index)
+                        .is_some_and(|line| visible_lines.contains(&line))
+                })
+                .collect(),
+            Err(_) => return,
+        };
+        if visible_links.is_empty() {
+            return;
+        }
+
+        self.link_hints.typed.clear();
+        self.link_hints.hints = hint_labels(visible_links.len())
+            .into_iter()
+            .zip(visible_links)
+            .map(|(label, (link_index, span_index))| LinkHint {
+                label,
+                link_index,
+                span_index,
+            })
+            .collect();
+        self.state = AppState::LinkHints;
+    }
+
+    /// Narrows the hints down by one more typed character,
+    /// following the link once its whole label has been typed
+    pub fn type_link_hint(&mut self, c: char) {
+        self.link_hints.typed.push(c);
+        let matching_hints: Vec<&LinkHint> = self.link_hints.matching_hints().collect();
+        match matching_hints.as_slice() {
+            [] => self.state = AppState::Article,
+            [hint] if hint.label == self.link_hints.typed => {
+                self.article.selected_link_index = hint.link_index;
+                self.state = AppState::Article;
+                self.view_selected_article_from_selected_link();
+            }
+            _ => {}
+        }
+    }
+
+    /// Re-runs the in-article search for the current input and jumps to the first match
+    /// at or below the top of the screen
+    pub fn update_article_search(&mut self) {
+        if let Ok(spans) = self.article.markdown_spans.try_lock() {
+            self.article_search.find_matches(&spans);
+        }
+        if let Some(layout) = self.article.layout() {
+            let first_visible_match = self.article_search.matches.iter().position(|text_match| {
+                layout
+                    .line_of_span_offset(text_match.span_index, text_match.start)
+                    .is_some_and(|line| line >= self.article.vertical_scroll)
+            });
+            self.article_search.current_match = first_visible_match.unwrap_or(0);
+        }
+        self.scroll_to_current_match();
+    }
+
+    pub fn scroll_article_search(&mut self, direction: ScrollDirection) {
+        self.article_search.scroll_match(direction);
+        self.scroll_to_current_match();
+    }
+
+    fn scroll_to_current_match(&mut self) {
+        if let Some(text_match) = self.article_search.current() {
+            self.article
+                .scroll_to_span_offset(text_match.span_index, text_match.start);
+        }
+    }
+
     fn load_page_from_history(&mut self) {
-        if let Some(title) = self.article.back_history.back() {
-            self.set_article_page(title.clone());
+        if let Some(page) = self.article.back_history.back().cloned() {
+            self.article.selected_link_index = 0;
+            self.article.vertical_scroll = 0;
+            self.set_article_page(page.title);
+            self.article.pending_view = Some(page.view);
         }
     }
 
     pub fn go_to_previous_article(&mut self) {
-        self.article.go_back_a_page();
-        self.load_page_from_history();
+        if self.article.go_back_a_page() {
+            self.load_page_from_history();
+        }
     }
 
     pub fn go_to_next_article(&mut self) {
-        self.article.go_forward_a_page();
-        self.load_page_from_history();
+        if self.article.go_forward_a_page() {
+            self.load_page_from_history();
+        }
     }
 }
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::parsing::{FormattedSpan, ListItem, TableData};
+
+// Narrowest a column is squeezed to before the table is cut off at the right edge
+const MIN_TABLE_COLUMN_WIDTH: usize = 3;
+// Columns each level of a nested list is indented by
+const LIST_INDENT_WIDTH: usize = 2;
+// Bullets for each level of nesting, going back to the first past the last
+const LIST_BULLETS: [&str; 3] = ["•", "◦", "▪"];
+// Drawn down the left of a block quote, once for each level of nesting
+const QUOTE_BAR: &str = "│ ";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FragmentKind {
+    Text,
+    TableBorder,
+    TableHeader,
+    TableCell,
+    /// A list item's bullet or number, or the indentation lining its wrapped lines up after it
+    ListMarker,
+    QuoteBar,
+}
+
+/// A piece of one span that lands on a single rendered line
+#[derive(Clone, Debug)]
+pub struct Fragment {
+    pub span_index: usize,
+    /// Byte offset of `text` within the span's text, for `Text` fragments
+    pub span_offset: usize,
+    pub text: String,
+    pub kind: FragmentKind,
+}
+
+/// Where every span of an article ends up once it is word-wrapped to a given width,
+/// mirroring what is drawn on screen line for line.
+pub struct ArticleLayout {
+    pub lines: Vec<Vec<Fragment>>,
+    span_lines: HashMap<usize, usize>,
+}
+
+impl ArticleLayout {
+    pub fn new(spans: &[FormattedSpan], width: usize) -> Self {
+        let mut layout = ArticleLayout {
+            lines: Vec::new(),
+            span_lines: HashMap::new(),
+        };
+        let width = width.max(1);
+
+        for logical_line in spans.split(|formatted_span| formatted_span.is_break) {
+            if let Some(table_span) = logical_line
+                .iter()
+                .find(|formatted_span| formatted_span.table.is_some())
+            {
+                layout
+                    .span_lines
+                    .insert(table_span.index, layout.lines.len());
+                let table = table_span.table.as_ref().unwrap();
+                layout
+                    .lines
+                    .extend(table_lines(table, table_span.index, width));
+                continue;
+            }
+            layout.wrap_logical_line(logical_line, width);
+        }
+
+        layout
+    }
+
+    /// Word-wraps one line of text. A list item's first line starts with its marker,
+    /// and the lines it wraps onto are indented to line up with its text.
+    fn wrap_logical_line(&mut self, logical_line: &[FormattedSpan], width: usize) {
+        let (first_prefix, hanging_indent) = match logical_line.first() {
+            Some(first_span) => line_prefixes(first_span, width),
+            None => (vec![], vec![]),
+        };
+        // Where text starts on each line, after any quote bars, list marker or indentation
+        let line_start: usize = hanging_indent
+            .iter()
+            .map(|fragment| fragment.text.width())
+            .sum();
+        let keeps_indentation = logical_line
+            .first()
+            .is_some_and(|formatted_span| formatted_span.is_code_block);
+
+        let mut current_line: Vec<Fragment> = first_prefix;
+        let mut current_width = line_start;
+
+        for formatted_span in logical_line {
+            for (offset, token) in split_into_tokens(&formatted_span.text) {
+                let is_whitespace = token.chars().all(char::is_whitespace);
+                let token_width = token.width();
+
+                if is_whitespace {
+                    // Whitespace is dropped at the start of a line and where a line wraps,
+                    // other than the indentation of code
+                    if current_width == line_start && !keeps_indentation {
+                        continue;
+                    }
+                    if current_width + token_width < width {
+                        self.note_span_line(formatted_span.index);
+                        push_fragment(&mut current_line, formatted_span.index, offset, token);
+                        current_width += token_width;
+                    } else {
+                        self.lines
+                            .push(std::mem::replace(&mut current_line, hanging_indent.clone()));
+                        current_width = line_start;
+                    }
+                    continue;
+                }
+
+                if current_width + token_width > width && current_width > line_start {
+                    self.lines
+                        .push(std::mem::replace(&mut current_line, hanging_indent.clone()));
+                    current_width = line_start;
+                }
+
+                self.note_span_line(formatted_span.index);
+                if token_width <= width.saturating_sub(line_start) {
+                    push_fragment(&mut current_line, formatted_span.index, offset, token);
+                    current_width += token_width;
+                    continue;
+                }
+
+                // Words wider than the whole line are broken wherever they run out of room
+                let mut piece_start = 0;
+                for (char_offset, c) in token.char_indices() {
+                    let char_width = c.width().unwrap_or(0);
+                    if current_width + char_width > width && current_width > line_start {
+                        push_fragment(
+                            &mut current_line,
+                            formatted_span.index,
+                            offset + piece_start,
+                            &token[piece_start..char_offset],
+                        );
+                        self.lines
+                            .push(std::mem::replace(&mut current_line, hanging_indent.clone()));
+                        current_width = line_start;
+                        piece_start = char_offset;
+                    }
+                    current_width += char_width;
+                }
+                push_fragment(
+                    &mut current_line,
+                    formatted_span.index,
+                    offset + piece_start,
+                    &token[piece_start..],
+                );
+            }
+            // Spans with no visible text still need a position
+            self.note_span_line(formatted_span.index);
+        }
+
+        self.lines.push(current_line);
+    }
+
+    /// Records the line being filled as where the span starts, unless it was seen earlier
+    fn note_span_line(&mut self, span_index: usize) {
+        self.span_lines
+            .entry(span_index)
+            .or_insert(self.lines.len());
+    }
+
+    pub fn total_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Furthest the article can scroll while still filling a viewport `height` lines tall
+    pub fn max_scroll(&self, height: usize) -> usize {
+        self.total_lines().saturating_sub(height)
+    }
+
+    /// The rendered line that a span starts on
+    pub fn line_of_span(&self, span_index: usize) -> Option<usize> {
+        self.span_lines.get(&span_index).copied()
+    }
+
+    /// The rendered line holding the given byte offset of a span's text
+    pub fn line_of_span_offset(&self, span_index: usize, offset: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|fragments| {
+                fragments.iter().any(|fragment| {
+                    fragment.span_index == span_index
+                        && fragment.kind == FragmentKind::Text
+                        && fragment.span_offset + fragment.text.len() > offset
+                })
+            })
+            .or_else(|| self.line_of_span(span_index))
+    }
+
+    /// The line and column where a span's first visible text is drawn
+    pub fn position_of_span(&self, span_index: usize) -> Option<(usize, usize)> {
+        let line = self.line_of_span(span_index)?;
+        let mut column = 0;
+        for fragment in self.lines.get(line)? {
+            if fragment.span_index == span_index {
+                return Some((line, column));
+            }
+            column += fragment.text.width();
+        }
+        None
+    }
+
+    /// The last heading that starts on or above `line`, ie. the section `line` is in
+    pub fn heading_at_line<'a>(
+        &self,
+        spans: &'a [FormattedSpan],
+        line: usize,
+    ) -> Option<&'a FormattedSpan> {
+        spans
+            .iter()
+            .filter(|formatted_span| formatted_span.is_heading)
+            .take_while(|heading| {
+                self.line_of_span(heading.index)
+                    .is_some_and(|heading_line| heading_line <= line)
+            })
+            .last()
+    }
+}
+
+/// The fragments drawn before the text of a line's first row, and of the rows it wraps onto:
+/// a bar for each block quote it is in, then any list marker or the indentation under one
+fn line_prefixes(first_span: &FormattedSpan, width: usize) -> (Vec<Fragment>, Vec<Fragment>) {
+    let fragment = |text: String, kind: FragmentKind| Fragment {
+        span_index: first_span.index,
+        span_offset: 0,
+        text,
+        kind,
+    };
+    let mut first_prefix = Vec::new();
+    let mut hanging_indent = Vec::new();
+    let quote_bars = QUOTE_BAR.repeat(first_span.quote_depth);
+    if !quote_bars.is_empty() {
+        first_prefix.push(fragment(quote_bars.clone(), FragmentKind::QuoteBar));
+        hanging_indent.push(fragment(quote_bars.clone(), FragmentKind::QuoteBar));
+    }
+    if let Some(list_item) = first_span.list_item {
+        let (marker, hanging_width) =
+            list_marker(&list_item, width.saturating_sub(quote_bars.width()));
+        first_prefix.push(fragment(marker, FragmentKind::ListMarker));
+        hanging_indent.push(fragment(
+            " ".repeat(hanging_width),
+            FragmentKind::ListMarker,
+        ));
+    }
+    (first_prefix, hanging_indent)
+}
+
+/// The marker drawn before a list item's first line, and how wide it is, so how far the
+/// item's wrapped lines are indented. Deeply nested items are indented less on narrow screens,
+/// keeping at least half the width for their text.
+fn list_marker(list_item: &ListItem, width: usize) -> (String, usize) {
+    let bullet = match list_item.number {
+        Some(number) => format!("{number}."),
+        None => LIST_BULLETS[list_item.depth % LIST_BULLETS.len()].to_string(),
+    };
+    let bullet_width = bullet.width() + 1;
+    let indent =
+        (list_item.depth * LIST_INDENT_WIDTH).min((width / 2).saturating_sub(bullet_width));
+    let marker = match list_item.is_continuation {
+        // Lines carrying on an item start under its text, with its marker left blank
+        true => " ".repeat(indent + bullet_width),
+        false => format!("{}{bullet} ", " ".repeat(indent)),
+    };
+    (marker, indent + bullet_width)
+}
+
+/// Adds `text` onto the end of the line, merging it into the previous fragment
+/// if both come from the same span
+fn push_fragment(line: &mut Vec<Fragment>, span_index: usize, span_offset: usize, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(last_fragment) = line.last_mut() {
+        if last_fragment.span_index == span_index
+            && last_fragment.kind == FragmentKind::Text
+            && last_fragment.span_offset + last_fragment.text.len() == span_offset
+        {
+            last_fragment.text.push_str(text);
+            return;
+        }
+    }
+    line.push(Fragment {
+        span_index,
+        span_offset,
+        text: text.to_string(),
+        kind: FragmentKind::Text,
+    });
+}
+
+/// Splits text into alternating runs of whitespace and non-whitespace, with their byte offsets
+fn split_into_tokens(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut token_start = 0;
+    let mut in_whitespace = None;
+    for (offset, c) in text.char_indices() {
+        let is_whitespace = c.is_whitespace();
+        if in_whitespace.is_some_and(|was_whitespace| was_whitespace != is_whitespace) {
+            tokens.push((token_start, &text[token_start..offset]));
+            token_start = offset;
+        }
+        in_whitespace = Some(is_whitespace);
+    }
+    if token_start < text.len() {
+        tokens.push((token_start, &text[token_start..]));
+    }
+    tokens
+}
+
+/// Lays a table out as a bordered grid no wider than `max_width`,
+/// squeezing the widest columns and truncating their cells to fit.
+fn table_lines(table: &TableData, span_index: usize, max_width: usize) -> Vec<Vec<Fragment>> {
+    let column_count = table.column_count();
+    let mut column_widths = vec![0; column_count];
+    for row in table.rows.iter() {
+        for (column, cell) in row.iter().enumerate() {
+            column_widths[column] = column_widths[column].max(cell.width());
+        }
+    }
+
+    // Each column has a space of padding either side plus a border to its left,
+    // with one more border on the far right
+    let grid_width = |widths: &Vec<usize>| widths.iter().map(|width| width + 3).sum::<usize>() + 1;
+    while grid_width(&column_widths) > max_width {
+        let (widest_column, &widest) = column_widths
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, &width)| width)
+            .unwrap();
+        if widest <= MIN_TABLE_COLUMN_WIDTH {
+            break;
+        }
+        column_widths[widest_column] -= 1;
+    }
+
+    let fragment = |text: String, kind: FragmentKind| Fragment {
+        span_index,
+        span_offset: 0,
+        text,
+        kind,
+    };
+    let border_line = |left: &str, middle: &str, right: &str| -> Vec<Fragment> {
+        let segments: Vec<String> = column_widths
+            .iter()
+            .map(|width| "─".repeat(width + 2))
+            .collect();
+        vec![fragment(
+            truncate_to_width(
+                &format!("{left}{}{right}", segments.join(middle)),
+                max_width,
+            ),
+            FragmentKind::TableBorder,
+        )]
+    };
+
+    let mut lines = vec![border_line("┌", "┬", "┐")];
+    for (row_index, row) in table.rows.iter().enumerate() {
+        let cell_kind = match row_index {
+            0 => FragmentKind::TableHeader,
+            _ => FragmentKind::TableCell,
+        };
+        let mut fragments = vec![fragment(String::from("│"), FragmentKind::TableBorder)];
+        for (column, width) in column_widths.iter().enumerate() {
+            let cell = truncate_to_width(row.get(column).map_or("", |cell| cell), *width);
+            let padding = " ".repeat(width - cell.width());
+            fragments.push(fragment(format!(" {cell}{padding} "), cell_kind));
+            fragments.push(fragment(String::from("│"), FragmentKind::TableBorder));
+        }
+        lines.push(truncate_fragments_to_width(fragments, max_width));
+        if row_index == 0 && table.rows.len() > 1 {
+            lines.push(border_line("├", "┼", "┤"));
+        }
+    }
+    lines.push(border_line("└", "┴", "┘"));
+    lines
+}
+
+pub fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if width + char_width + 1 > max_width {
+            break;
+        }
+        truncated.push(c);
+        width += char_width;
+    }
+    if max_width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+fn truncate_fragments_to_width(fragments: Vec<Fragment>, max_width: usize) -> Vec<Fragment> {
+    let mut remaining_width = max_width;
+    let mut truncated_fragments = Vec::new();
+    for mut fragment in fragments {
+        if remaining_width == 0 {
+            break;
+        }
+        let fragment_width = fragment.text.width();
+        if fragment_width <= remaining_width {
+            remaining_width -= fragment_width;
+        } else {
+            fragment.text = truncate_to_width(&fragment.text, remaining_width);
+            remaining_width = 0;
+        }
+        truncated_fragments.push(fragment);
+    }
+    truncated_fragments
+}
@@ -0,0 +1,117 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader},
+    path::PathBuf,
+};
+
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::now_secs;
+use crate::wikipedia::WikiSource;
+
+const BOOKMARKS_FILE: &str = ".config/wik/bookmarks.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub title: String,
+    pub wiki: WikiSource,
+    /// Heading of the section that was on screen when the bookmark was made
+    #[serde(default)]
+    pub section: Option<String>,
+    /// Unix timestamp (seconds) of when the bookmark was made
+    pub created_at: u64,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Bookmark {
+    pub fn new(title: String, wiki: WikiSource, section: Option<String>) -> Self {
+        Bookmark {
+            title,
+            wiki,
+            section,
+            created_at: now_secs(),
+            tags: vec![],
+        }
+    }
+
+    /// Whether every word of `filter` appears in the title, section, wiki or tags, ignoring case
+    pub fn matches(&self, filter: &str) -> bool {
+        let searchable_text = format!(
+            "{} {} {} {}",
+            self.title,
+            self.section.as_deref().unwrap_or(""),
+            self.wiki.name,
+            self.tags.join(" ")
+        )
+        .to_lowercase();
+        filter
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| searchable_text.contains(word))
+    }
+}
+
+/// Every saved bookmark, newest last, kept in sync with `~/.config/wik/bookmarks.json`
+#[derive(Debug, Default)]
+pub struct BookmarkStore {
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl BookmarkStore {
+    fn file_path() -> Option<PathBuf> {
+        home_dir().map(|home_dir_path| home_dir_path.join(BOOKMARKS_FILE))
+    }
+
+    pub fn load() -> Self {
+        let bookmarks = Self::file_path()
+            .and_then(|file_path| File::options().read(true).write(false).open(file_path).ok())
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+        BookmarkStore { bookmarks }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(file_path) = Self::file_path() {
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(file_path, serde_json::to_string_pretty(&self.bookmarks)?)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a bookmark, replacing any earlier one for the same page and section
+    pub fn add(&mut self, bookmark: Bookmark) -> io::Result<()> {
+        self.bookmarks.retain(|existing| {
+            !(existing.title == bookmark.title
+                && existing.wiki == bookmark.wiki
+                && existing.section == bookmark.section)
+        });
+        self.bookmarks.push(bookmark);
+        self.save()
+    }
+
+    pub fn remove(&mut self, index: usize) -> io::Result<()> {
+        if index < self.bookmarks.len() {
+            self.bookmarks.remove(index);
+        }
+        self.save()
+    }
+
+    pub fn set_tags(&mut self, index: usize, tags: Vec<String>) -> io::Result<()> {
+        if let Some(bookmark) = self.bookmarks.get_mut(index) {
+            bookmark.tags = tags;
+        }
+        self.save()
+    }
+
+    /// Indices of the bookmarks matching `filter`, newest first
+    pub fn filtered_indices(&self, filter: &str) -> Vec<usize> {
+        (0..self.bookmarks.len())
+            .rev()
+            .filter(|&index| self.bookmarks[index].matches(filter))
+            .collect()
+    }
+}
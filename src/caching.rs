@@ -3,66 +3,152 @@ use std::{
     error::Error,
     fs::{self, File},
     io::{self, BufReader, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use dirs::home_dir;
-use rand::{distributions::Alphanumeric, Rng};
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::utils::now_secs;
+
 pub type Url = String;
 pub type FileName = String;
 
+pub const DEFAULT_TTL_HOURS: u64 = 24;
+pub const DEFAULT_MAX_SIZE_MB: u64 = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub file_name: FileName,
+    /// Unix timestamp (seconds) of when the wiki last confirmed this copy
+    pub fetched_at: u64,
+    /// Unix timestamp (seconds) of the last read or write, used for LRU eviction
+    pub last_accessed: u64,
+    pub etag: Option<String>,
+    pub size: u64,
+}
+
 #[derive(Debug)]
 pub struct CachingSession {
-    pub lookup_table: HashMap<Url, FileName>,
-    pub session_name: String,
+    pub lookup_table: HashMap<Url, CacheEntry>,
+    pub ttl_secs: u64,
+    pub max_size_bytes: u64,
+    /// Never go to the network, only serve what is already cached
+    pub offline: bool,
+    /// Plain text of the cached pages, for searching them without reading every file
+    pub page_texts: HashMap<Url, String>,
+    cache_dir: PathBuf,
+    /// Whether `lookup_table` has changes that are not yet in the index file
+    index_dirty: bool,
 }
 
 impl Default for CachingSession {
     fn default() -> Self {
         Self {
             lookup_table: HashMap::new(),
-            session_name: String::from("session_name"),
+            ttl_secs: DEFAULT_TTL_HOURS * 60 * 60,
+            max_size_bytes: DEFAULT_MAX_SIZE_MB * 1024 * 1024,
+            offline: false,
+            page_texts: HashMap::new(),
+            cache_dir: Self::default_cache_dir(),
+            index_dirty: false,
         }
     }
 }
 
 impl CachingSession {
     const WIK_DIR: &str = ".cache/wik/caches/";
+    const INDEX_FILE: &str = "index.json";
+
     pub fn new() -> Self {
-        let mut session = Self::default();
-        session.session_name = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(10)
-            .map(char::from)
-            .collect();
+        Self::in_dir(Self::default_cache_dir())
+    }
 
+    /// A session keeping its files in `cache_dir` instead of the user's cache directory
+    pub fn in_dir(cache_dir: PathBuf) -> Self {
+        let mut session = Self {
+            cache_dir,
+            ..Self::default()
+        };
+        session.load_index();
         session
     }
 
-    pub fn has_url(&self, url: &Url) -> bool {
-        self.lookup_table.contains_key(url)
+    pub fn set_limits(&mut self, ttl_hours: u64, max_size_mb: u64) {
+        self.ttl_secs = ttl_hours.saturating_mul(60 * 60);
+        self.max_size_bytes = max_size_mb.saturating_mul(1024 * 1024);
+        self.evict_least_recently_used(None);
+        self.save_index().unwrap_or(());
+    }
+
+    /// Whether the cached copy of `url` is young enough to be used without asking the wiki
+    pub fn is_fresh(&self, url: &Url) -> bool {
+        match self.lookup_table.get(url) {
+            Some(entry) => now_secs().saturating_sub(entry.fetched_at) < self.ttl_secs,
+            None => false,
+        }
+    }
+
+    pub fn get_etag(&self, url: &Url) -> Option<String> {
+        self.lookup_table
+            .get(url)
+            .and_then(|entry| entry.etag.clone())
     }
 
-    fn cache_dir() -> PathBuf {
+    fn default_cache_dir() -> PathBuf {
         home_dir().unwrap().join(Self::WIK_DIR)
     }
 
-    fn session_cache_dir(&self) -> PathBuf {
-        Self::cache_dir().join(format!("{}/", self.session_name))
+    fn index_file_path(&self) -> PathBuf {
+        self.cache_dir.join(Self::INDEX_FILE)
     }
 
     pub fn get_cache_file_path(&self, file_name: &str) -> PathBuf {
-        //
-        self.session_cache_dir().join(file_name)
+        self.cache_dir.join(file_name)
+    }
+
+    fn load_index(&mut self) {
+        let file_result = File::options()
+            .read(true)
+            .write(false)
+            .open(self.index_file_path());
+        if let Ok(file) = file_result {
+            let reader = BufReader::new(file);
+            if let Ok(lookup_table) = serde_json::from_reader(reader) {
+                self.lookup_table = lookup_table;
+            }
+        }
+        // Forget about any entries whose files have been removed from under us
+        let cache_dir = &self.cache_dir;
+        self.lookup_table
+            .retain(|_, entry| cache_dir.join(&entry.file_name).is_file());
+    }
+
+    fn save_index(&mut self) -> io::Result<()> {
+        let index_path = self.index_file_path();
+        if let Some(parent) = index_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json_data = serde_json::to_string(&self.lookup_table)?;
+        fs::write(index_path, json_data)?;
+        self.index_dirty = false;
+        Ok(())
+    }
+
+    /// Writes out the access times and refreshes that have only been kept in memory, eg. on exit
+    pub fn flush_index(&mut self) -> io::Result<()> {
+        if self.index_dirty {
+            self.save_index()?;
+        }
+        Ok(())
     }
 
     pub fn write_to_cache<T: Serialize>(
         &mut self,
         url: &Url,
         serializable_object: T,
+        etag: Option<String>,
     ) -> Result<(), Box<dyn Error>> {
         let file_name = create_hash(url);
 
@@ -77,51 +163,224 @@ impl CachingSession {
         let json_data = serde_json::to_string(&serializable_object)?;
         file.write_all(json_data.as_bytes())?;
 
-        self.lookup_table.insert(url.clone(), file_name);
+        let now = now_secs();
+        self.lookup_table.insert(
+            url.clone(),
+            CacheEntry {
+                file_name,
+                fetched_at: now,
+                last_accessed: now,
+                etag,
+                size: json_data.len() as u64,
+            },
+        );
+
+        self.evict_least_recently_used(Some(url));
+        self.save_index()?;
 
         Ok(())
     }
 
-    pub fn get_from_cache<T: DeserializeOwned>(&self, url: &Url) -> Option<T> {
-        match self.lookup_table.get(url) {
-            Some(file_name) => {
-                // get from the file system
-                let file_result = File::options()
-                    .read(true)
-                    .write(false)
-                    .open(self.get_cache_file_path(file_name));
-                if let Err(_) = file_result {
-                    return None;
-                } else {
-                    let reader = BufReader::new(file_result.unwrap());
-                    match serde_json::from_reader::<_, T>(reader) {
-                        Ok(deserialized_object) => Some(deserialized_object),
-                        Err(_) => None,
-                    }
+    /// Marks the cached copy of `url` as confirmed by the wiki, eg. after a `304 Not Modified`
+    pub fn refresh(&mut self, url: &Url) {
+        if let Some(entry) = self.lookup_table.get_mut(url) {
+            entry.fetched_at = now_secs();
+            self.index_dirty = true;
+        }
+    }
+
+    pub fn get_from_cache<T: DeserializeOwned>(&mut self, url: &Url) -> Option<T> {
+        let entry = self.lookup_table.get_mut(url)?;
+        entry.last_accessed = now_secs();
+        self.index_dirty = true;
+
+        self.read_from_cache(url)
+    }
+
+    /// Reads a cached object without counting it as a use for LRU eviction
+    pub fn read_from_cache<T: DeserializeOwned>(&self, url: &Url) -> Option<T> {
+        let entry = self.lookup_table.get(url)?;
+        read_cache_file(&self.get_cache_file_path(&entry.file_name))
+    }
+
+    /// Every cached URL with the path of its file, for reading them without holding the session
+    pub fn cached_files(&self) -> Vec<(Url, PathBuf)> {
+        self.lookup_table
+            .iter()
+            .map(|(url, entry)| (url.clone(), self.get_cache_file_path(&entry.file_name)))
+            .collect()
+    }
+
+    fn total_size(&self) -> u64 {
+        self.lookup_table.values().map(|entry| entry.size).sum()
+    }
+
+    fn evict_least_recently_used(&mut self, url_to_keep: Option<&Url>) {
+        while self.total_size() > self.max_size_bytes && self.lookup_table.len() > 1 {
+            let oldest_url = self
+                .lookup_table
+                .iter()
+                .filter(|(url, _)| Some(*url) != url_to_keep)
+                .min_by_key(|(_, entry)| entry.last_accessed)
+                .map(|(url, _)| url.clone());
+
+            if let Some(url) = oldest_url {
+                self.page_texts.remove(&url);
+                if let Some(entry) = self.lookup_table.remove(&url) {
+                    fs::remove_file(self.get_cache_file_path(&entry.file_name)).unwrap_or(());
                 }
             }
-            None => None,
         }
     }
-    pub fn clear_caches() -> io::Result<()> {
-        match fs::remove_dir_all(Self::cache_dir()) {
-            Ok(_) => match fs::create_dir(Self::cache_dir()) {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    println!("Error creating files!");
-                    Err(e)
-                }
-            },
+
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.lookup_table.clear();
+        self.page_texts.clear();
+        self.index_dirty = false;
+        match fs::remove_dir_all(&self.cache_dir) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
             Err(e) => {
                 println!("Error removing files!");
+                return Err(e);
+            }
+        }
+        match fs::create_dir_all(&self.cache_dir) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                println!("Error creating files!");
                 Err(e)
             }
         }
     }
 }
 
+/// Reads an object from a cache file found with `CachingSession::cached_files`
+pub fn read_cache_file<T: DeserializeOwned>(file_path: &Path) -> Option<T> {
+    let file = File::options()
+        .read(true)
+        .write(false)
+        .open(file_path)
+        .ok()?;
+    serde_json::from_reader::<_, T>(BufReader::new(file)).ok()
+}
+
 fn create_hash(msg: &str) -> String {
     let mut hasher = Sha256::default();
     hasher.update(msg);
     format!("{:x}", hasher.finalize()).as_str()[0..10].to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A session over an empty directory of its own under the system temp dir
+    fn session_in_temp_dir(name: &str) -> CachingSession {
+        let cache_dir =
+            std::env::temp_dir().join(format!("wik-caching-test-{}-{}", std::process::id(), name));
+        fs::remove_dir_all(&cache_dir).unwrap_or(());
+        CachingSession::in_dir(cache_dir)
+    }
+
+    fn url(name: &str) -> Url {
+        format!("https://en.wikipedia.org/wiki/{}", name)
+    }
+
+    /// Caches a page whose file takes up exactly 100 bytes
+    fn write_page(session: &mut CachingSession, name: &str) {
+        session
+            .write_to_cache(&url(name), "x".repeat(98), None)
+            .unwrap();
+    }
+
+    #[test]
+    fn entries_go_stale_after_the_ttl() {
+        let mut session = session_in_temp_dir("ttl");
+        session.ttl_secs = 60;
+        write_page(&mut session, "Fresh");
+        write_page(&mut session, "Stale");
+        session
+            .lookup_table
+            .get_mut(&url("Stale"))
+            .unwrap()
+            .fetched_at = now_secs() - 61;
+
+        assert!(session.is_fresh(&url("Fresh")));
+        assert!(!session.is_fresh(&url("Stale")));
+        assert!(!session.is_fresh(&url("Missing")));
+
+        session.refresh(&url("Stale"));
+        assert!(session.is_fresh(&url("Stale")));
+        session.clear().unwrap();
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted_first() {
+        let mut session = session_in_temp_dir("lru");
+        session.max_size_bytes = 300;
+        for name in ["A", "B", "C"] {
+            write_page(&mut session, name);
+        }
+        // Timestamps only have a resolution of seconds, so order the uses by hand
+        for (name, last_accessed) in [("A", 3), ("B", 1), ("C", 2)] {
+            session
+                .lookup_table
+                .get_mut(&url(name))
+                .unwrap()
+                .last_accessed = last_accessed;
+        }
+        let evicted_file = session.get_cache_file_path(&session.lookup_table[&url("B")].file_name);
+
+        write_page(&mut session, "D");
+
+        assert!(!session.lookup_table.contains_key(&url("B")));
+        assert!(!evicted_file.exists());
+        for name in ["A", "C", "D"] {
+            assert!(session.lookup_table.contains_key(&url(name)));
+        }
+        session.clear().unwrap();
+    }
+
+    #[test]
+    fn the_cache_stays_under_its_size_cap() {
+        let mut session = session_in_temp_dir("size-cap");
+        session.max_size_bytes = 250;
+        for name in ["A", "B", "C", "D", "E"] {
+            write_page(&mut session, name);
+            assert!(session.total_size() <= session.max_size_bytes);
+        }
+        assert_eq!(session.lookup_table.len(), 2);
+        assert!(session.lookup_table.contains_key(&url("E")));
+
+        // A page bigger than the whole cache is still kept on its own
+        session.max_size_bytes = 50;
+        write_page(&mut session, "F");
+        assert_eq!(session.lookup_table.len(), 1);
+        assert!(session.lookup_table.contains_key(&url("F")));
+        session.clear().unwrap();
+    }
+
+    #[test]
+    fn reads_only_reach_the_index_file_when_flushed() {
+        let mut session = session_in_temp_dir("flush");
+        write_page(&mut session, "A");
+        session
+            .lookup_table
+            .get_mut(&url("A"))
+            .unwrap()
+            .last_accessed = 1;
+        session.save_index().unwrap();
+
+        let page: Option<String> = session.get_from_cache(&url("A"));
+        assert!(page.is_some());
+        let last_accessed_on_disk = |cache_dir: &PathBuf| {
+            CachingSession::in_dir(cache_dir.clone()).lookup_table[&url("A")].last_accessed
+        };
+        assert_eq!(last_accessed_on_disk(&session.cache_dir), 1);
+
+        session.flush_index().unwrap();
+        assert!(last_accessed_on_disk(&session.cache_dir) > 1);
+        session.clear().unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{format_timestamp, now_secs};
+use crate::wikipedia::WikiSource;
+
+const HISTORY_FILE: &str = ".config/wik/history.jsonl";
+/// Where the whole log was written at once, read if there is no `HISTORY_FILE` yet
+const OLD_HISTORY_FILE: &str = ".config/wik/history.json";
+/// Oldest visits are dropped once the log grows past this
+const MAX_HISTORY_ENTRIES: usize = 5000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub title: String,
+    pub wiki: WikiSource,
+    /// Unix timestamp (seconds) of the visit
+    pub visited_at: u64,
+}
+
+impl HistoryEntry {
+    /// Whether every word of `filter` appears in the title or wiki, ignoring case
+    pub fn matches(&self, filter: &str) -> bool {
+        let searchable_text = format!("{} {}", self.title, self.wiki.name).to_lowercase();
+        filter
+            .to_lowercase()
+            .split_whitespace()
+            .all(|word| searchable_text.contains(word))
+    }
+
+    /// The day of the visit, used to group the history screen
+    pub fn day(&self) -> String {
+        format_timestamp(self.visited_at, "%A, %-d %B %Y")
+    }
+}
+
+/// Every article visited, oldest first, kept in sync with `~/.config/wik/history.jsonl`
+/// where each visit is appended as a line of JSON
+#[derive(Debug, Default)]
+pub struct HistoryLog {
+    pub entries: Vec<HistoryEntry>,
+    /// Titles in `entries` for each wiki, kept up to date so links can be styled every frame
+    visited_titles: HashMap<WikiSource, HashSet<String>>,
+}
+
+impl HistoryLog {
+    fn file_path() -> Option<PathBuf> {
+        home_dir().map(|home_dir_path| home_dir_path.join(HISTORY_FILE))
+    }
+
+    fn open_file(file_path: PathBuf) -> Option<File> {
+        File::options().read(true).write(false).open(file_path).ok()
+    }
+
+    pub fn load() -> Self {
+        let file_path = match Self::file_path() {
+            Some(file_path) => file_path,
+            None => return HistoryLog::default(),
+        };
+        let mut log = match Self::open_file(file_path) {
+            Some(file) => HistoryLog {
+                // A line cut short by a crash is skipped rather than losing the rest
+                entries: BufReader::new(file)
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter_map(|line| serde_json::from_str(&line).ok())
+                    .collect(),
+                ..Default::default()
+            },
+            None => {
+                let entries = home_dir()
+                    .and_then(|home_dir_path| Self::open_file(home_dir_path.join(OLD_HISTORY_FILE)))
+                    .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+                    .unwrap_or_default();
+                let log = HistoryLog {
+                    entries,
+                    ..Default::default()
+                };
+                if !log.entries.is_empty() {
+                    log.save().unwrap_or(());
+                }
+                log
+            }
+        };
+        // Appending leaves dropped visits in the file, so they are cleared out here
+        if log.entries.len() > MAX_HISTORY_ENTRIES {
+            log.drop_oldest_entries();
+            log.save().unwrap_or(());
+        }
+        log.index_visited_titles();
+        log
+    }
+
+    fn index_visited_titles(&mut self) {
+        self.visited_titles.clear();
+        for entry in self.entries.iter() {
+            self.visited_titles
+                .entry(entry.wiki.clone())
+                .or_default()
+                .insert(entry.title.clone());
+        }
+    }
+
+    /// Writes out the whole log, replacing the file
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(file_path) = Self::file_path() {
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut lines = String::new();
+            for entry in self.entries.iter() {
+                lines.push_str(&serde_json::to_string(entry)?);
+                lines.push('\n');
+            }
+            fs::write(file_path, lines)?;
+        }
+        Ok(())
+    }
+
+    /// Adds one visit to the end of the file
+    fn append(entry: &HistoryEntry) -> io::Result<()> {
+        if let Some(file_path) = Self::file_path() {
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = File::options().create(true).append(true).open(file_path)?;
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        Ok(())
+    }
+
+    fn drop_oldest_entries(&mut self) {
+        if self.entries.len() > MAX_HISTORY_ENTRIES {
+            let excess = self.entries.len() - MAX_HISTORY_ENTRIES;
+            self.entries.drain(..excess);
+            // A dropped title may still have later visits, so count them again
+            self.index_visited_titles();
+        }
+    }
+
+    /// Logs a visit to `title` now, unless it is the same page as the last visit
+    pub fn record(&mut self, title: String, wiki: WikiSource) -> io::Result<()> {
+        if let Some(last_entry) = self.entries.last() {
+            if last_entry.title == title && last_entry.wiki == wiki {
+                return Ok(());
+            }
+        }
+        let entry = HistoryEntry {
+            title,
+            wiki,
+            visited_at: now_secs(),
+        };
+        let result = Self::append(&entry);
+        self.visited_titles
+            .entry(entry.wiki.clone())
+            .or_default()
+            .insert(entry.title.clone());
+        self.entries.push(entry);
+        self.drop_oldest_entries();
+        result
+    }
+
+    /// Titles of every article visited on `wiki`, or None if nothing has been read there
+    pub fn visited_titles(&self, wiki: &WikiSource) -> Option<&HashSet<String>> {
+        self.visited_titles.get(wiki)
+    }
+
+    /// Indices of the entries matching `filter`, newest first
+    pub fn filtered_indices(&self, filter: &str) -> Vec<usize> {
+        (0..self.entries.len())
+            .rev()
+            .filter(|&index| self.entries[index].matches(filter))
+            .collect()
+    }
+}
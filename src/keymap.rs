@@ -0,0 +1,483 @@
+use std::{collections::HashMap, error::Error, fs, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use dirs::home_dir;
+use serde::Deserialize;
+
+use crate::app::AppState;
+
+const KEYMAP_FILE: &str = ".config/wik/keys.toml";
+
+/// Everything a key can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    Quit,
+    /// Opens the menu, or in an article first clears any in-article search
+    OpenMenu,
+    /// Leaves a menu or overlay for the screen beneath it
+    Close,
+    Confirm,
+    SelectPrevious,
+    SelectNext,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    ScrollToTop,
+    ScrollToBottom,
+    PreviousLink,
+    NextLink,
+    FollowLink,
+    LinkHints,
+    /// Goes back to the previous article
+    Back,
+    /// Goes forward again after going back
+    Forward,
+    ScrollInfoboxUp,
+    ScrollInfoboxDown,
+    ToggleInfobox,
+    OpenContents,
+    FindInArticle,
+    NextMatch,
+    PreviousMatch,
+    CursorLeft,
+    CursorRight,
+    CursorToStart,
+    CursorToEnd,
+    DeleteBackward,
+    CommandPalette,
+    /// Bookmarks the article at the section on screen
+    AddBookmark,
+    OpenBookmarks,
+    OpenHistory,
+    /// Opens the selected link in a tab after the current one
+    OpenLinkInNewTab,
+    NextTab,
+    PreviousTab,
+    CloseTab,
+    /// Goes from one pane, to two side by side, to two one above the other
+    ToggleSplit,
+    /// Moves focus to the other pane of a split
+    SwitchPane,
+    /// Opens the selected link in the other pane, splitting the screen if needed
+    FollowLinkInOtherPane,
+    /// Deletes the selected entry of a list
+    Delete,
+    EditTags,
+    /// Opens the theme editor on the highlighted theme
+    EditTheme,
+}
+
+/// A group of bindings in the keymap file, one per screen plus a few shared ones.
+/// A screen's own section wins over `menu`/`text`, which win over `global`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeymapSection {
+    Global,
+    /// Shared by every menu
+    Menu,
+    /// Shared by every text box
+    Text,
+    Title,
+    Search,
+    SearchMenu,
+    Credit,
+    Article,
+    ArticleSearch,
+    LinkHints,
+    ArticleMenu,
+    Contents,
+    ThemeMenu,
+    CommandPalette,
+    Bookmarks,
+    History,
+    ThemeEditor,
+}
+
+impl KeymapSection {
+    /// Sections searched for a key pressed in `state`, most specific first
+    fn for_state(state: &AppState) -> &'static [KeymapSection] {
+        use KeymapSection::*;
+        match state {
+            AppState::Title => &[Title, Text, Global],
+            AppState::Search => &[Search, Text, Global],
+            AppState::SearchMenu => &[SearchMenu, Menu, Global],
+            AppState::Credit => &[Credit, Menu, Global],
+            AppState::Article => &[Article, Global],
+            AppState::ArticleSearch => &[ArticleSearch, Text, Global],
+            AppState::LinkHints => &[LinkHints, Global],
+            AppState::ArticleMenu => &[ArticleMenu, Menu, Global],
+            AppState::Contents => &[Contents, Menu, Global],
+            AppState::ThemeMenu => &[ThemeMenu, Menu, Global],
+            AppState::CommandPalette => &[CommandPalette, Text, Global],
+            AppState::Bookmarks => &[Bookmarks, Text, Global],
+            AppState::History => &[History, Text, Global],
+            AppState::ThemeEditor => &[ThemeEditor, Text, Global],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeymapPreset {
+    #[default]
+    Default,
+    Vim,
+    Emacs,
+}
+
+type Binding = (KeymapSection, &'static str, Action);
+
+const DEFAULT_BINDINGS: &[Binding] = &[
+    (KeymapSection::Global, "ctrl-p", Action::CommandPalette),
+    (KeymapSection::Title, "enter", Action::Confirm),
+    (KeymapSection::Title, "esc", Action::Quit),
+    (KeymapSection::Search, "esc", Action::OpenMenu),
+    (KeymapSection::Search, "f1", Action::Quit),
+    (KeymapSection::Search, "enter", Action::Confirm),
+    (KeymapSection::Search, "up", Action::SelectPrevious),
+    (KeymapSection::Search, "down", Action::SelectNext),
+    (KeymapSection::SearchMenu, "esc", Action::Close),
+    (KeymapSection::SearchMenu, "f1", Action::Quit),
+    (KeymapSection::Menu, "esc", Action::Close),
+    (KeymapSection::Menu, "enter", Action::Confirm),
+    (KeymapSection::Menu, "up", Action::SelectPrevious),
+    (KeymapSection::Menu, "down", Action::SelectNext),
+    (KeymapSection::Text, "backspace", Action::DeleteBackward),
+    (KeymapSection::Text, "left", Action::CursorLeft),
+    (KeymapSection::Text, "right", Action::CursorRight),
+    (KeymapSection::Text, "ctrl-left", Action::CursorToStart),
+    (KeymapSection::Text, "ctrl-right", Action::CursorToEnd),
+    (KeymapSection::Article, "esc", Action::OpenMenu),
+    (KeymapSection::Article, "left", Action::PreviousLink),
+    (KeymapSection::Article, "right", Action::NextLink),
+    (KeymapSection::Article, "enter", Action::FollowLink),
+    (KeymapSection::Article, "shift-up", Action::ScrollInfoboxUp),
+    (
+        KeymapSection::Article,
+        "shift-down",
+        Action::ScrollInfoboxDown,
+    ),
+    (KeymapSection::Article, "up", Action::ScrollUp),
+    (KeymapSection::Article, "down", Action::ScrollDown),
+    (KeymapSection::Article, "pageup", Action::PageUp),
+    (KeymapSection::Article, "pagedown", Action::PageDown),
+    (KeymapSection::Article, "ctrl-u", Action::HalfPageUp),
+    (KeymapSection::Article, "ctrl-d", Action::HalfPageDown),
+    (KeymapSection::Article, "home", Action::ScrollToTop),
+    (KeymapSection::Article, "end", Action::ScrollToBottom),
+    (KeymapSection::Article, "i", Action::ToggleInfobox),
+    (KeymapSection::Article, "t", Action::OpenContents),
+    (KeymapSection::Article, "/", Action::FindInArticle),
+    (KeymapSection::Article, "n", Action::NextMatch),
+    (KeymapSection::Article, "N", Action::PreviousMatch),
+    (KeymapSection::Article, "f", Action::LinkHints),
+    (KeymapSection::Article, "b", Action::AddBookmark),
+    (KeymapSection::Article, "B", Action::OpenBookmarks),
+    (KeymapSection::Article, "h", Action::OpenHistory),
+    (KeymapSection::Article, "o", Action::OpenLinkInNewTab),
+    (KeymapSection::Article, "tab", Action::NextTab),
+    (KeymapSection::Article, "shift-tab", Action::PreviousTab),
+    (KeymapSection::Article, "x", Action::CloseTab),
+    (KeymapSection::Article, "s", Action::ToggleSplit),
+    (KeymapSection::Article, "w", Action::SwitchPane),
+    (KeymapSection::Article, "O", Action::FollowLinkInOtherPane),
+    (KeymapSection::ArticleSearch, "esc", Action::Close),
+    (KeymapSection::ArticleSearch, "enter", Action::Confirm),
+    (KeymapSection::LinkHints, "esc", Action::Close),
+    (
+        KeymapSection::LinkHints,
+        "backspace",
+        Action::DeleteBackward,
+    ),
+    (KeymapSection::Bookmarks, "esc", Action::Close),
+    (KeymapSection::Bookmarks, "enter", Action::Confirm),
+    (KeymapSection::Bookmarks, "up", Action::SelectPrevious),
+    (KeymapSection::Bookmarks, "down", Action::SelectNext),
+    (KeymapSection::Bookmarks, "delete", Action::Delete),
+    (KeymapSection::Bookmarks, "ctrl-t", Action::EditTags),
+    (KeymapSection::History, "esc", Action::Close),
+    (KeymapSection::History, "enter", Action::Confirm),
+    (KeymapSection::History, "up", Action::SelectPrevious),
+    (KeymapSection::History, "down", Action::SelectNext),
+    (KeymapSection::ThemeMenu, "e", Action::EditTheme),
+    (KeymapSection::ThemeEditor, "esc", Action::Close),
+    (KeymapSection::ThemeEditor, "enter", Action::Confirm),
+    (KeymapSection::ThemeEditor, "up", Action::SelectPrevious),
+    (KeymapSection::ThemeEditor, "down", Action::SelectNext),
+    (KeymapSection::ThemeEditor, "tab", Action::SelectNext),
+    (
+        KeymapSection::ThemeEditor,
+        "shift-tab",
+        Action::SelectPrevious,
+    ),
+];
+
+const VIM_BINDINGS: &[Binding] = &[
+    (KeymapSection::CommandPalette, "ctrl-n", Action::SelectNext),
+    (
+        KeymapSection::CommandPalette,
+        "ctrl-p",
+        Action::SelectPrevious,
+    ),
+    (KeymapSection::Article, "j", Action::ScrollDown),
+    (KeymapSection::Article, "k", Action::ScrollUp),
+    (KeymapSection::Article, "h", Action::PreviousLink),
+    (KeymapSection::Article, "l", Action::NextLink),
+    // `h` is taken by links, so the history goes where vim keeps its own, `q:`
+    (KeymapSection::Article, "q", Action::OpenHistory),
+    (KeymapSection::Article, "J", Action::ScrollInfoboxDown),
+    (KeymapSection::Article, "K", Action::ScrollInfoboxUp),
+    (KeymapSection::Article, "H", Action::Back),
+    (KeymapSection::Article, "L", Action::Forward),
+    (KeymapSection::Article, "ctrl-f", Action::PageDown),
+    (KeymapSection::Article, "ctrl-b", Action::PageUp),
+    (KeymapSection::Article, "g", Action::ScrollToTop),
+    (KeymapSection::Article, "G", Action::ScrollToBottom),
+    (KeymapSection::Article, "ctrl-w", Action::SwitchPane),
+    (KeymapSection::Menu, "j", Action::SelectNext),
+    (KeymapSection::Menu, "k", Action::SelectPrevious),
+    (KeymapSection::Menu, "q", Action::Close),
+    (KeymapSection::Search, "ctrl-n", Action::SelectNext),
+    (KeymapSection::Search, "ctrl-k", Action::SelectPrevious),
+    (KeymapSection::Bookmarks, "ctrl-n", Action::SelectNext),
+    (KeymapSection::Bookmarks, "ctrl-k", Action::SelectPrevious),
+    (KeymapSection::History, "ctrl-n", Action::SelectNext),
+    (KeymapSection::History, "ctrl-k", Action::SelectPrevious),
+    (KeymapSection::ThemeEditor, "ctrl-n", Action::SelectNext),
+    (KeymapSection::ThemeEditor, "ctrl-k", Action::SelectPrevious),
+    (KeymapSection::Text, "ctrl-h", Action::DeleteBackward),
+];
+
+const EMACS_BINDINGS: &[Binding] = &[
+    (KeymapSection::Global, "alt-x", Action::CommandPalette),
+    (KeymapSection::CommandPalette, "ctrl-n", Action::SelectNext),
+    (
+        KeymapSection::CommandPalette,
+        "ctrl-p",
+        Action::SelectPrevious,
+    ),
+    (KeymapSection::CommandPalette, "ctrl-g", Action::Close),
+    (KeymapSection::Article, "alt-n", Action::ScrollDown),
+    (KeymapSection::Article, "alt-p", Action::ScrollUp),
+    (KeymapSection::Article, "ctrl-b", Action::PreviousLink),
+    (KeymapSection::Article, "ctrl-f", Action::NextLink),
+    (KeymapSection::Article, "ctrl-v", Action::PageDown),
+    (KeymapSection::Article, "alt-v", Action::PageUp),
+    (KeymapSection::Article, "alt-<", Action::ScrollToTop),
+    (KeymapSection::Article, "alt->", Action::ScrollToBottom),
+    (KeymapSection::Article, "ctrl-s", Action::FindInArticle),
+    (KeymapSection::Article, "ctrl-g", Action::OpenMenu),
+    (KeymapSection::Article, "l", Action::Back),
+    (KeymapSection::Article, "r", Action::Forward),
+    (KeymapSection::ArticleSearch, "ctrl-g", Action::Close),
+    (KeymapSection::ArticleSearch, "ctrl-s", Action::Confirm),
+    (KeymapSection::LinkHints, "ctrl-g", Action::Close),
+    (KeymapSection::Menu, "alt-n", Action::SelectNext),
+    (KeymapSection::Menu, "alt-p", Action::SelectPrevious),
+    (KeymapSection::Menu, "ctrl-g", Action::Close),
+    (KeymapSection::Search, "alt-n", Action::SelectNext),
+    (KeymapSection::Search, "alt-p", Action::SelectPrevious),
+    (KeymapSection::Search, "ctrl-g", Action::OpenMenu),
+    (KeymapSection::Bookmarks, "alt-n", Action::SelectNext),
+    (KeymapSection::Bookmarks, "alt-p", Action::SelectPrevious),
+    (KeymapSection::Bookmarks, "ctrl-g", Action::Close),
+    (KeymapSection::Bookmarks, "ctrl-d", Action::Delete),
+    (KeymapSection::History, "alt-n", Action::SelectNext),
+    (KeymapSection::History, "alt-p", Action::SelectPrevious),
+    (KeymapSection::History, "ctrl-g", Action::Close),
+    (KeymapSection::ThemeEditor, "alt-n", Action::SelectNext),
+    (KeymapSection::ThemeEditor, "alt-p", Action::SelectPrevious),
+    (KeymapSection::ThemeEditor, "ctrl-g", Action::Close),
+    (KeymapSection::Text, "ctrl-a", Action::CursorToStart),
+    (KeymapSection::Text, "ctrl-e", Action::CursorToEnd),
+    (KeymapSection::Text, "ctrl-b", Action::CursorLeft),
+    (KeymapSection::Text, "ctrl-f", Action::CursorRight),
+    (KeymapSection::Text, "ctrl-h", Action::DeleteBackward),
+];
+
+/// A key along with the modifiers held with it. Shift is folded into the character
+/// for printable keys, so `N` and `shift-n` are the same binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => KeyBinding {
+                code: KeyCode::Char(c.to_ascii_uppercase()),
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            // Terminals report shift-tab as a key of its own, with or without shift held
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyBinding {
+                code: KeyCode::BackTab,
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            KeyCode::BackTab => KeyBinding {
+                code: KeyCode::BackTab,
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            _ => KeyBinding { code, modifiers },
+        }
+    }
+
+    /// Parses keys written like `j`, `G`, `ctrl-d`, `shift-up`, `alt-<` or `f1`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key_name = text;
+        // A lone "-" is a key in its own right, so only split on dashes followed by something
+        while let Some((modifier_name, rest)) = key_name.split_once('-') {
+            if rest.is_empty() {
+                break;
+            }
+            modifiers |= match modifier_name.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => {
+                    return Err(format!(
+                        "unknown modifier \"{modifier_name}\" in \"{text}\""
+                    ))
+                }
+            };
+            key_name = rest;
+        }
+
+        let code = match key_name.to_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            function_key if function_key.len() > 1 && function_key.starts_with('f') => {
+                match function_key[1..].parse::<u8>() {
+                    Ok(number) => KeyCode::F(number),
+                    Err(_) => return Err(format!("unknown key \"{key_name}\" in \"{text}\"")),
+                }
+            }
+            _ => {
+                let mut chars = key_name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("unknown key \"{key_name}\" in \"{text}\"")),
+                }
+            }
+        };
+        Ok(KeyBinding::new(code, modifiers))
+    }
+}
+
+impl From<KeyEvent> for KeyBinding {
+    fn from(key: KeyEvent) -> Self {
+        KeyBinding::new(key.code, key.modifiers)
+    }
+}
+
+/// The character a key types into a text box, for keys without Ctrl or Alt held
+pub fn typed_char(key: KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(c)
+            if !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            Some(c)
+        }
+        _ => None,
+    }
+}
+
+/// Layout of `keys.toml`: an optional preset to start from,
+/// then tables of `"key" = "Action"` named after keymap sections
+#[derive(Deserialize)]
+struct KeymapFile {
+    #[serde(default)]
+    preset: KeymapPreset,
+    #[serde(flatten)]
+    sections: HashMap<KeymapSection, HashMap<String, Action>>,
+}
+
+pub struct Keymap {
+    bindings: HashMap<KeymapSection, HashMap<KeyBinding, Action>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_preset(KeymapPreset::Default)
+    }
+}
+
+impl Keymap {
+    pub fn from_preset(preset: KeymapPreset) -> Self {
+        let mut keymap = Keymap {
+            bindings: HashMap::new(),
+        };
+        let preset_bindings = match preset {
+            KeymapPreset::Default => &[],
+            KeymapPreset::Vim => VIM_BINDINGS,
+            KeymapPreset::Emacs => EMACS_BINDINGS,
+        };
+        for &(section, key, action) in DEFAULT_BINDINGS.iter().chain(preset_bindings) {
+            let key_binding = KeyBinding::parse(key).expect("Built-in key bindings should parse");
+            keymap.bind(section, key_binding, action);
+        }
+        keymap
+    }
+
+    fn file_path() -> Option<PathBuf> {
+        home_dir().map(|home_dir_path| home_dir_path.join(KEYMAP_FILE))
+    }
+
+    /// Loads `~/.config/wik/keys.toml` over the top of its preset,
+    /// or the default keymap if there is no such file
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        match Keymap::file_path() {
+            Some(file_path) if file_path.is_file() => {
+                Keymap::from_toml(&fs::read_to_string(file_path)?)
+            }
+            _ => Ok(Keymap::default()),
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error>> {
+        let keymap_file: KeymapFile = toml::from_str(text)?;
+        let mut keymap = Keymap::from_preset(keymap_file.preset);
+        for (section, section_bindings) in keymap_file.sections {
+            for (key, action) in section_bindings {
+                keymap.bind(section, KeyBinding::parse(&key)?, action);
+            }
+        }
+        Ok(keymap)
+    }
+
+    fn bind(&mut self, section: KeymapSection, key_binding: KeyBinding, action: Action) {
+        self.bindings
+            .entry(section)
+            .or_default()
+            .insert(key_binding, action);
+    }
+
+    /// The action `key` is bound to on the screen for `state`, if any
+    pub fn action_for(&self, state: &AppState, key: KeyEvent) -> Option<Action> {
+        let key_binding = KeyBinding::from(key);
+        KeymapSection::for_state(state)
+            .iter()
+            .find_map(|section| self.bindings.get(section)?.get(&key_binding).copied())
+    }
+}
@@ -1,6 +1,11 @@
 pub mod app;
+pub mod article_layout;
+pub mod bookmarks;
 pub mod caching;
+pub mod history;
+pub mod keymap;
 pub mod parsing;
+pub mod session;
 pub mod styles;
 pub mod ui;
 pub mod utils;
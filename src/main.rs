@@ -1,6 +1,11 @@
 mod app;
+mod article_layout;
+mod bookmarks;
 mod caching;
+mod history;
+mod keymap;
 mod parsing;
+mod session;
 mod styles;
 mod ui;
 mod utils;
@@ -8,22 +13,27 @@ mod widgets;
 mod wikipedia;
 
 use app::{ActionMenu, App, AppState, ScrollDirection, TypeableState};
-use caching::CachingSession;
 use clap::Parser;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
     execute,
     terminal::{
         disable_raw_mode, enable_raw_mode, size, EnterAlternateScreen, LeaveAlternateScreen,
     },
 };
 use dialoguer::Input;
+use history::HistoryLog;
+use keymap::{typed_char, Action, Keymap};
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::Rect;
 use ratatui::{Terminal, TerminalOptions, Viewport};
+use session::Session;
 use std::io;
 use std::{error::Error, time::Duration};
+use styles::ColorSupport;
 use utils::clargs::{load_arg_from_config, save_arg_to_file, Args};
+use utils::{format_timestamp, shared_copy};
+use wikipedia::WikiSource;
 
 const APP_REFRESH_TIME_MILLIS: u64 = 16;
 // const APP_DEFAULT_MARGIN: u16 = 2;
@@ -31,8 +41,21 @@ const APP_REFRESH_TIME_MILLIS: u64 = 16;
 fn main() -> Result<(), Box<dyn Error>> {
     let mut args = Args::parse();
 
+    if args.history {
+        for entry in HistoryLog::load().entries {
+            println!(
+                "{}  {}  ({})",
+                format_timestamp(entry.visited_at, "%Y-%m-%d %H:%M"),
+                entry.title,
+                entry.wiki.name
+            );
+        }
+        return Ok(());
+    }
+
     let mut app = App::new();
     app.is_running = true;
+    app.set_color_support(args.color.unwrap_or_else(ColorSupport::detect));
 
     // Check if the user has saved configurations
     if let Some(loaded_args) = load_arg_from_config() {
@@ -73,6 +96,39 @@ fn main() -> Result<(), Box<dyn Error>> {
         args.margin = get_dimension("margin size");
     }
 
+    app.wiki = WikiSource::from_args(args.lang.as_deref(), args.wiki.as_deref());
+
+    {
+        let mut cache = app.cache.lock().unwrap();
+        if args.clear_cache {
+            cache.clear()?;
+        }
+        cache.set_limits(args.cache_ttl, args.cache_size);
+        cache.offline = args.offline;
+    }
+    if args.offline {
+        wikipedia::load_cached_page_texts(shared_copy(&app.cache));
+    }
+
+    match Keymap::load() {
+        Ok(keymap) => app.keymap = keymap,
+        Err(e) => eprintln!("Could not load keys.toml, using the default keys: {}", e),
+    }
+    for e in app.theme_menu.load_errors.iter() {
+        eprintln!("Could not load theme, skipping it: {}", e);
+    }
+
+    app.article.snap_links_to_scroll = args.snap_links;
+    if let Some(theme_name) = &args.theme {
+        if !app.apply_theme_named(theme_name) {
+            eprintln!(
+                "No theme called \"{}\", using the default theme",
+                theme_name
+            );
+        }
+    }
+    app.config = args.clone();
+
     if let Some(query) = args.search {
         app.search_and_load(query.clone());
     }
@@ -83,6 +139,17 @@ fn main() -> Result<(), Box<dyn Error>> {
         app.try_getting_page(title.clone());
     }
 
+    if args.restore {
+        match Session::load() {
+            Some(session) => app.restore_session(session),
+            None => eprintln!("No saved session to restore"),
+        }
+    }
+
+    if args.bookmarks {
+        app.open_bookmarks();
+    }
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -111,148 +178,350 @@ fn main() -> Result<(), Box<dyn Error>> {
             break;
         }
         terminal.draw(|f| ui::draw(f, &app))?;
+        app.restore_pending_views();
+        app.record_pending_visits();
 
         if event::poll(Duration::from_millis(APP_REFRESH_TIME_MILLIS))? {
             if let Event::Key(key) = event::read()? {
+                let action = app.keymap.action_for(&app.state, key);
+                if action == Some(Action::Quit) {
+                    app.is_running = false;
+                    continue;
+                }
+                if action == Some(Action::CommandPalette) {
+                    app.open_command_palette();
+                    continue;
+                }
                 match app.state {
-                    AppState::Title => match key.code {
+                    AppState::Title => match action {
                         // MARK: - Title State
-                        KeyCode::Enter => {
+                        Some(Action::Confirm) => {
                             // app.state = AppState::Search;
                             app.search_and_load(app.title.input.clone());
                             // app.search.input = app.title.input.clone();
                             // app.load_wikipedia_search_query();
                         }
-                        KeyCode::Esc => {
-                            app.is_running = false;
-                        }
                         _ => {
-                            app.title.handle_key(key);
+                            app.title.handle_key(action, key);
                         }
                     },
-                    AppState::Search => match key.code {
+                    AppState::Search => match action {
                         // MARK: - Search State
-                        KeyCode::Esc => {
+                        Some(Action::OpenMenu) => {
                             // Enter Escape menu, from where one can exit normally
                             app.state = AppState::SearchMenu;
                         }
-                        KeyCode::F(1) => {
-                            // Just-in-case exit
-                            app.is_running = false
-                        }
                         // MARK: - the josh mann bookmark
-                        KeyCode::Enter => {
+                        Some(Action::Confirm) => {
                             if app.search.text_box_is_highlighted {
                                 app.load_wikipedia_search_query();
                             } else {
                                 app.view_selected_article_from_search();
                             }
                         }
-                        KeyCode::Up => {
+                        Some(Action::SelectPrevious) => {
                             app.search.scroll_results(ScrollDirection::UP);
                         }
-                        KeyCode::Down => {
+                        Some(Action::SelectNext) => {
                             app.search.scroll_results(ScrollDirection::DOWN);
                         }
 
                         _ => {
-                            app.search.handle_key(key);
+                            app.search.handle_key(action, key);
                         }
                     },
-                    AppState::SearchMenu => match key.code {
+                    AppState::SearchMenu => match action {
                         // MARK: - Search Menu State
-                        KeyCode::Esc => {
+                        Some(Action::Close) => {
                             app.state = AppState::Search;
                         }
 
-                        KeyCode::Up => {
-                            app.search_menu.scroll(ScrollDirection::UP);
-                        }
-
-                        KeyCode::Down => {
-                            app.search_menu.scroll(ScrollDirection::DOWN);
-                        }
-
-                        KeyCode::Enter => {
+                        Some(Action::Confirm) => {
                             app.search_menu.get_selected_action()(&mut app);
                         }
 
-                        KeyCode::F(1) => {
-                            // Just-in-case exit
-                            app.is_running = false;
-                        }
-                        _ => {}
+                        Some(action) => app.search_menu.handle_action(action),
+                        None => {}
                     },
-                    AppState::Credit => match key.code {
+                    AppState::Credit => match action {
                         // MARK: - Credit State
-                        KeyCode::Esc => {
+                        Some(Action::Close) => {
                             app.state = AppState::SearchMenu;
                         }
 
-                        KeyCode::Up => {
-                            app.credit.scroll(ScrollDirection::UP);
-                        }
-                        KeyCode::Down => {
-                            app.credit.scroll(ScrollDirection::DOWN);
-                        }
-
-                        KeyCode::Enter => {
+                        Some(Action::Confirm) => {
                             app.credit.get_selected_action()(&mut app);
                         }
 
-                        _ => {}
+                        Some(action) => app.credit.handle_action(action),
+                        None => {}
                     },
-                    AppState::Article => match key.code {
+                    AppState::Article => match action {
                         // MARK: - Article State
-                        KeyCode::Esc => {
+                        Some(Action::OpenMenu) if app.article_search.is_active() => {
+                            app.article_search.clear();
+                        }
+                        Some(Action::OpenMenu) => {
                             app.state = AppState::ArticleMenu;
                         }
-                        KeyCode::Left => {
+                        Some(Action::FindInArticle) => {
+                            app.article_search.move_cursor_to_end();
+                            app.state = AppState::ArticleSearch;
+                        }
+                        Some(Action::LinkHints) => {
+                            app.open_link_hints();
+                        }
+                        Some(Action::NextMatch) => {
+                            app.scroll_article_search(ScrollDirection::DOWN);
+                        }
+                        Some(Action::PreviousMatch) => {
+                            app.scroll_article_search(ScrollDirection::UP);
+                        }
+                        Some(Action::PreviousLink) => {
                             app.article.scroll_link(ScrollDirection::UP);
                         }
-                        KeyCode::Right => {
+                        Some(Action::NextLink) => {
                             app.article.scroll_link(ScrollDirection::DOWN);
                         }
-                        KeyCode::Up => {
+                        Some(Action::ScrollInfoboxUp) => {
+                            app.article.scroll_infobox(ScrollDirection::UP);
+                        }
+                        Some(Action::ScrollInfoboxDown) => {
+                            app.article.scroll_infobox(ScrollDirection::DOWN);
+                        }
+                        Some(Action::ScrollUp) => {
                             app.article.scroll_vertically(ScrollDirection::UP);
                         }
-                        KeyCode::Down => {
+                        Some(Action::ScrollDown) => {
                             app.article.scroll_vertically(ScrollDirection::DOWN);
                         }
-                        KeyCode::Enter => {
+                        Some(Action::PageUp) => {
+                            app.article.scroll_page(ScrollDirection::UP);
+                        }
+                        Some(Action::PageDown) => {
+                            app.article.scroll_page(ScrollDirection::DOWN);
+                        }
+                        Some(Action::HalfPageUp) => {
+                            app.article.scroll_half_page(ScrollDirection::UP);
+                        }
+                        Some(Action::HalfPageDown) => {
+                            app.article.scroll_half_page(ScrollDirection::DOWN);
+                        }
+                        Some(Action::ScrollToTop) => {
+                            app.article.scroll_to_top();
+                        }
+                        Some(Action::ScrollToBottom) => {
+                            app.article.scroll_to_bottom();
+                        }
+                        Some(Action::ToggleInfobox) => {
+                            app.article.toggle_infobox();
+                        }
+                        Some(Action::OpenContents) => {
+                            app.open_contents();
+                        }
+                        Some(Action::Back) => {
+                            app.go_to_previous_article();
+                        }
+                        Some(Action::Forward) => {
+                            app.go_to_next_article();
+                        }
+                        Some(Action::FollowLink) => {
                             app.view_selected_article_from_selected_link();
                         }
+                        Some(Action::OpenLinkInNewTab) => {
+                            app.open_selected_link_in_new_tab();
+                        }
+                        Some(Action::NextTab) => {
+                            app.cycle_tab(ScrollDirection::DOWN);
+                        }
+                        Some(Action::PreviousTab) => {
+                            app.cycle_tab(ScrollDirection::UP);
+                        }
+                        Some(Action::CloseTab) => {
+                            app.close_tab();
+                        }
+                        Some(Action::ToggleSplit) => {
+                            app.cycle_split();
+                        }
+                        Some(Action::SwitchPane) => {
+                            app.switch_pane();
+                        }
+                        Some(Action::FollowLinkInOtherPane) => {
+                            app.follow_link_in_other_pane();
+                        }
+                        Some(Action::AddBookmark) => {
+                            app.add_bookmark();
+                        }
+                        Some(Action::OpenBookmarks) => {
+                            app.open_bookmarks();
+                        }
+                        Some(Action::OpenHistory) => {
+                            app.open_history();
+                        }
                         _ => {}
                     },
-                    AppState::ArticleMenu => match key.code {
-                        // MARK: - Article Menu State
-                        KeyCode::Esc => {
+                    AppState::ArticleSearch => match action {
+                        // MARK: - Article Search State
+                        Some(Action::Close) => {
+                            app.article_search.clear();
                             app.state = AppState::Article;
                         }
-                        KeyCode::Up => {
-                            app.article_menu.scroll(ScrollDirection::UP);
+                        Some(Action::Confirm) => {
+                            app.state = AppState::Article;
                         }
-
-                        KeyCode::Down => {
-                            app.article_menu.scroll(ScrollDirection::DOWN);
+                        _ => {
+                            app.article_search.handle_key(action, key);
+                            app.update_article_search();
+                        }
+                    },
+                    AppState::LinkHints => match action {
+                        // MARK: - Link Hints State
+                        Some(Action::Close) => {
+                            app.state = AppState::Article;
+                        }
+                        Some(Action::DeleteBackward) => {
+                            app.link_hints.typed.pop();
+                        }
+                        Some(_) => {}
+                        None => {
+                            if let Some(c) = typed_char(key) {
+                                app.type_link_hint(c.to_ascii_lowercase());
+                            }
+                        }
+                    },
+                    AppState::ArticleMenu => match action {
+                        // MARK: - Article Menu State
+                        Some(Action::Close) => {
+                            app.state = AppState::Article;
                         }
 
-                        KeyCode::Enter => {
+                        Some(Action::Confirm) => {
                             app.article_menu.get_selected_action()(&mut app);
                         }
-                        _ => {}
+                        Some(action) => app.article_menu.handle_action(action),
+                        None => {}
+                    },
+                    AppState::Contents => match action {
+                        // MARK: - Contents State
+                        Some(Action::Close) => {
+                            app.state = AppState::Article;
+                        }
+                        Some(Action::Confirm) => {
+                            app.contents_menu.get_selected_action()(&mut app);
+                        }
+                        Some(action) => app.contents_menu.handle_action(action),
+                        None => {}
                     },
-                    AppState::ThemeMenu => match key.code {
+                    AppState::ThemeMenu => match action {
                         // MARK: - Theme State
-                        KeyCode::Enter => {
+                        Some(Action::Confirm) => {
                             app.theme_menu.get_selected_action()(&mut app);
                         }
-                        KeyCode::Esc => {
-                            app.state = AppState::Search;
-                            // app.article.selected_link_index += 1;
+                        Some(Action::Close) => {
+                            app.close_theme_menu();
+                        }
+                        Some(Action::EditTheme) => {
+                            app.open_theme_editor();
+                        }
+                        Some(action) => {
+                            app.theme_menu.handle_action(action);
+                            app.preview_selected_theme();
+                        }
+                        None => {}
+                    },
+                    AppState::ThemeEditor => match action {
+                        // MARK: - Theme Editor State
+                        Some(Action::Close) => {
+                            app.close_theme_editor();
+                        }
+                        Some(Action::Confirm) => {
+                            app.save_edited_theme();
+                        }
+                        Some(Action::SelectPrevious) => {
+                            app.theme_editor.select_field(ScrollDirection::UP);
+                        }
+                        Some(Action::SelectNext) => {
+                            app.theme_editor.select_field(ScrollDirection::DOWN);
+                        }
+                        _ => {
+                            let old_input = app.theme_editor.input.clone();
+                            app.theme_editor.handle_key(action, key);
+                            if app.theme_editor.input != old_input {
+                                app.apply_theme_editor_input();
+                            }
+                        }
+                    },
+                    AppState::CommandPalette => match action {
+                        // MARK: - Command Palette State
+                        Some(Action::Close) => {
+                            app.state = app.command_palette.return_state;
+                        }
+                        Some(Action::Confirm) => {
+                            app.run_command_palette_selection();
+                        }
+                        Some(action @ (Action::SelectPrevious | Action::SelectNext)) => {
+                            app.command_palette.handle_action(action);
+                        }
+                        _ => {
+                            app.command_palette.handle_key(action, key);
+                            app.command_palette.filter_items();
+                        }
+                    },
+                    AppState::Bookmarks => match action {
+                        // MARK: - Bookmarks State
+                        Some(Action::Close) if app.bookmarks.editing_tags.is_some() => {
+                            app.bookmarks.finish_editing_tags(false);
+                        }
+                        Some(Action::Confirm) if app.bookmarks.editing_tags.is_some() => {
+                            app.bookmarks.finish_editing_tags(true);
+                        }
+                        Some(Action::Close) => {
+                            app.state = app.bookmarks.return_state;
+                        }
+                        Some(Action::Confirm) => {
+                            app.open_selected_bookmark();
+                        }
+                        Some(Action::SelectPrevious) => {
+                            app.bookmarks.scroll_bookmarks(ScrollDirection::UP);
+                        }
+                        Some(Action::SelectNext) => {
+                            app.bookmarks.scroll_bookmarks(ScrollDirection::DOWN);
+                        }
+                        Some(Action::Delete) => {
+                            app.bookmarks.delete_selected();
+                        }
+                        Some(Action::EditTags) => {
+                            app.bookmarks.start_editing_tags();
+                        }
+                        _ => {
+                            let old_filter = app.bookmarks.filter.clone();
+                            app.bookmarks.handle_key(action, key);
+                            if app.bookmarks.filter != old_filter {
+                                app.bookmarks.selected_index = 0;
+                            }
+                        }
+                    },
+                    AppState::History => match action {
+                        // MARK: - History State
+                        Some(Action::Close) => {
+                            app.state = app.history.return_state;
+                        }
+                        Some(Action::Confirm) => {
+                            app.open_selected_history_entry();
+                        }
+                        Some(Action::SelectPrevious) => {
+                            app.history.scroll_entries(ScrollDirection::UP);
+                        }
+                        Some(Action::SelectNext) => {
+                            app.history.scroll_entries(ScrollDirection::DOWN);
+                        }
+                        _ => {
+                            let old_filter = app.history.filter.clone();
+                            app.history.handle_key(action, key);
+                            if app.history.filter != old_filter {
+                                app.history.selected_index = 0;
+                            }
                         }
-                        // KeyCode::Left => {}
-                        _ => app.theme_menu.handle_key(key),
                     }, // _ => app.is_running = false,
                 }
             }
@@ -267,7 +536,12 @@ fn main() -> Result<(), Box<dyn Error>> {
         DisableMouseCapture
     )?;
 
-    CachingSession::clear_caches()?;
+    if let Err(e) = app.session().save() {
+        eprintln!("Could not save the session: {}", e);
+    }
+    if let Err(e) = app.cache.lock().unwrap().flush_index() {
+        eprintln!("Could not save the cache index: {}", e);
+    }
 
     Ok(())
 }
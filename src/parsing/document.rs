@@ -0,0 +1,411 @@
+use std::ops::Range;
+
+use pulldown_cmark::{Event, OffsetIter, Options, Parser, Tag, TagEnd};
+
+use crate::{
+    parsing::{FormattedSpan, InlineStyle, ListItem, TableData},
+    utils::cut_off_from_char,
+};
+
+/// An article as a tree of blocks, each holding its text as inline content.
+/// Built by a CommonMark parser from the Markdown that htmd makes of a page,
+/// then flattened into the `FormattedSpan`s that are laid out and drawn.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Block {
+    Heading { level: usize, content: Vec<Inline> },
+    Paragraph(Vec<Inline>),
+    List(Vec<ListEntry>),
+    Quote(Vec<Block>),
+    Code(String),
+    Table(TableData),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListEntry {
+    /// The item's number in an ordered list, `None` in a bulleted one
+    pub number: Option<usize>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Inline {
+    Text(String),
+    Code(String),
+    Emphasis(Vec<Inline>),
+    Strong(Vec<Inline>),
+    Strikethrough(Vec<Inline>),
+    Link {
+        target: String,
+        content: Vec<Inline>,
+    },
+    LineBreak,
+}
+
+impl Inline {
+    /// The text of inline content with its formatting and links dropped
+    pub fn plain_text(inlines: &[Inline]) -> String {
+        inlines
+            .iter()
+            .map(|inline| match inline {
+                Inline::Text(text) | Inline::Code(text) => text.clone(),
+                Inline::Emphasis(content)
+                | Inline::Strong(content)
+                | Inline::Strikethrough(content)
+                | Inline::Link { content, .. } => Inline::plain_text(content),
+                Inline::LineBreak => String::from(" "),
+            })
+            .collect()
+    }
+}
+
+impl Document {
+    pub fn from_markdown(markdown: &str) -> Self {
+        let mut events = EventReader {
+            markdown,
+            events: Parser::new_ext(
+                markdown,
+                Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+            )
+            .into_offset_iter(),
+            event_range: 0..0,
+        };
+        Document {
+            blocks: read_blocks(&mut events),
+        }
+    }
+
+    /// Flattens the document into spans, one per run of text with the same formatting and link,
+    /// with a line break span ending every line and a blank line after each block
+    pub fn spans(&self) -> Vec<FormattedSpan> {
+        let mut writer = SpanWriter::default();
+        for block in self.blocks.iter() {
+            writer.write_block(block);
+        }
+        writer.spans
+    }
+}
+
+/// The CommonMark parser's events, along with where in the Markdown the last one came from
+struct EventReader<'a> {
+    markdown: &'a str,
+    events: OffsetIter<'a>,
+    event_range: Range<usize>,
+}
+
+impl<'a> Iterator for EventReader<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        let (event, event_range) = self.events.next()?;
+        self.event_range = event_range;
+        Some(event)
+    }
+}
+
+/// Reads blocks up to the end of the element they are in, or of the document.
+/// Inline content outside of a paragraph, as in the items of a tight list, is put in one.
+fn read_blocks(events: &mut EventReader) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut loose_inlines: Vec<Inline> = Vec::new();
+
+    while let Some(event) = events.next() {
+        let block = match event {
+            Event::End(_) => break,
+            Event::Start(Tag::Paragraph) => Block::Paragraph(read_inlines(events)),
+            Event::Start(Tag::Heading { level, .. }) => Block::Heading {
+                level: level as usize,
+                content: read_inlines(events),
+            },
+            Event::Start(Tag::BlockQuote(_)) => Block::Quote(read_blocks(events)),
+            Event::Start(Tag::CodeBlock(_)) => Block::Code(read_code(events)),
+            Event::Start(Tag::List(start)) => Block::List(read_list_entries(events, start)),
+            Event::Start(Tag::Table(_)) => Block::Table(read_table(events)),
+            event => {
+                loose_inlines.extend(read_inline(event, events));
+                continue;
+            }
+        };
+        if !loose_inlines.is_empty() {
+            blocks.push(Block::Paragraph(std::mem::take(&mut loose_inlines)));
+        }
+        blocks.push(block);
+    }
+
+    if !loose_inlines.is_empty() {
+        blocks.push(Block::Paragraph(loose_inlines));
+    }
+    blocks
+}
+
+/// Reads inline content up to the end of the element it is in
+fn read_inlines(events: &mut EventReader) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    while let Some(event) = events.next() {
+        match event {
+            Event::End(_) => break,
+            event => inlines.extend(read_inline(event, events)),
+        }
+    }
+    inlines
+}
+
+/// The inline content that starts with `event`, reading the rest of it if it is an element.
+/// Images, raw HTML and anything else without text to show come out empty.
+fn read_inline<'a>(event: Event<'a>, events: &mut EventReader<'a>) -> Vec<Inline> {
+    match event {
+        Event::Text(text) => vec![Inline::Text(text.into_string())],
+        Event::Code(code) => vec![Inline::Code(code.into_string())],
+        Event::SoftBreak => vec![Inline::Text(String::from(" "))],
+        Event::HardBreak => vec![Inline::LineBreak],
+        Event::Start(Tag::Emphasis) => vec![Inline::Emphasis(read_inlines(events))],
+        Event::Start(Tag::Strong) => vec![Inline::Strong(read_inlines(events))],
+        Event::Start(Tag::Strikethrough) => vec![Inline::Strikethrough(read_inlines(events))],
+        Event::Start(Tag::Link { dest_url, .. }) => vec![Inline::Link {
+            target: dest_url.into_string(),
+            content: read_inlines(events),
+        }],
+        Event::Start(Tag::Image { .. }) => {
+            read_inlines(events);
+            vec![]
+        }
+        // Anything else is flattened into its content
+        Event::Start(_) => read_inlines(events),
+        _ => vec![],
+    }
+}
+
+fn read_code(events: &mut EventReader) -> String {
+    let mut code = String::new();
+    for event in events.by_ref() {
+        match event {
+            Event::Text(text) => code.push_str(&text),
+            Event::End(_) => break,
+            _ => {}
+        }
+    }
+    code
+}
+
+/// Reads the items of a list. Ordered items keep the numbers they were written with, as
+/// CommonMark runs two ordered lists that are only separated by a blank line into one.
+fn read_list_entries(events: &mut EventReader, start: Option<u64>) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::Item) => {
+                let written_number = events.markdown[events.event_range.clone()]
+                    .trim_start()
+                    .split(|c: char| !c.is_ascii_digit())
+                    .next()
+                    .and_then(|digits| digits.parse().ok());
+                let number =
+                    start.map(|start| written_number.unwrap_or(start as usize + entries.len()));
+                entries.push(ListEntry {
+                    number,
+                    blocks: read_blocks(events),
+                });
+            }
+            Event::End(TagEnd::List(_)) => break,
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Reads a table's rows, the header first, with each cell's content flattened to plain text
+fn read_table(events: &mut EventReader) -> TableData {
+    let mut rows = Vec::new();
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::TableHead | Tag::TableRow) => {
+                let mut cells = Vec::new();
+                while let Some(event) = events.next() {
+                    match event {
+                        Event::Start(Tag::TableCell) => {
+                            cells.push(Inline::plain_text(&read_inlines(events)))
+                        }
+                        Event::End(_) => break,
+                        _ => {}
+                    }
+                }
+                rows.push(cells);
+            }
+            Event::End(TagEnd::Table) => break,
+            _ => {}
+        }
+    }
+    TableData { rows }
+}
+
+#[derive(Default)]
+struct SpanWriter {
+    spans: Vec<FormattedSpan>,
+    /// The list item being written, marked as carried on once its first line is done
+    list_item: Option<ListItem>,
+    quote_depth: usize,
+    is_code_block: bool,
+}
+
+impl SpanWriter {
+    fn write_block(&mut self, block: &Block) {
+        match block {
+            Block::Heading { level, content } => {
+                self.spans.push(FormattedSpan {
+                    text: Inline::plain_text(content),
+                    is_heading: true,
+                    heading_level: *level,
+                    ..self.new_span()
+                });
+                self.push_break();
+            }
+            Block::Paragraph(content) => {
+                self.write_inlines(content, InlineStyle::default(), None);
+                self.push_break();
+            }
+            Block::List(entries) => {
+                let depth = self.list_item.map_or(0, |list_item| list_item.depth + 1);
+                let outer_list_item = self.list_item;
+                for entry in entries {
+                    self.list_item = Some(ListItem {
+                        depth,
+                        number: entry.number,
+                        is_continuation: false,
+                    });
+                    for (block_index, item_block) in entry.blocks.iter().enumerate() {
+                        // Paragraphs within one item are set apart, unlike the items themselves
+                        if block_index > 0 && !matches!(item_block, Block::List(_)) {
+                            self.push_break();
+                        }
+                        self.write_block(item_block);
+                    }
+                }
+                self.list_item = outer_list_item;
+            }
+            Block::Quote(blocks) => {
+                self.quote_depth += 1;
+                for (block_index, quoted_block) in blocks.iter().enumerate() {
+                    if block_index > 0 {
+                        self.push_break();
+                    }
+                    self.write_block(quoted_block);
+                }
+                self.quote_depth -= 1;
+            }
+            Block::Code(code) => {
+                let code_style = InlineStyle {
+                    code: true,
+                    ..InlineStyle::default()
+                };
+                self.is_code_block = true;
+                for line in code.lines() {
+                    self.push_text(line, code_style, None);
+                    self.push_break();
+                }
+                self.is_code_block = false;
+            }
+            Block::Table(table) => {
+                self.spans.push(FormattedSpan {
+                    table: Some(table.clone()),
+                    ..self.new_span()
+                });
+                self.push_break();
+            }
+        }
+        // Only top level blocks have a blank line after them
+        if self.list_item.is_none() && self.quote_depth == 0 {
+            self.push_break();
+        }
+    }
+
+    fn write_inlines(&mut self, inlines: &[Inline], style: InlineStyle, link: Option<&str>) {
+        for inline in inlines {
+            match inline {
+                Inline::Text(text) => self.push_text(text, style, link),
+                Inline::Code(code) => {
+                    let code_style = InlineStyle {
+                        code: true,
+                        ..style
+                    };
+                    self.push_text(code, code_style, link);
+                }
+                Inline::Emphasis(content) => {
+                    let italic_style = InlineStyle {
+                        italic: true,
+                        ..style
+                    };
+                    self.write_inlines(content, italic_style, link);
+                }
+                Inline::Strong(content) => {
+                    let bold_style = InlineStyle {
+                        bold: true,
+                        ..style
+                    };
+                    self.write_inlines(content, bold_style, link);
+                }
+                Inline::Strikethrough(content) => {
+                    let strikethrough_style = InlineStyle {
+                        strikethrough: true,
+                        ..style
+                    };
+                    self.write_inlines(content, strikethrough_style, link);
+                }
+                Inline::Link { target, content } => {
+                    self.write_inlines(content, style, Some(cut_off_from_char(target, '#')))
+                }
+                Inline::LineBreak => self.push_break(),
+            }
+        }
+    }
+
+    /// Adds text to the line, joining it onto the last span if that has the same formatting and link
+    fn push_text(&mut self, text: &str, style: InlineStyle, link: Option<&str>) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(last_span) = self.spans.last_mut() {
+            if !last_span.is_break
+                && !last_span.is_heading
+                && last_span.table.is_none()
+                && last_span.style == style
+                && last_span.link.as_deref() == link
+            {
+                last_span.text.push_str(text);
+                return;
+            }
+        }
+        self.spans.push(FormattedSpan {
+            text: text.to_string(),
+            link: link.map(str::to_string),
+            style,
+            ..self.new_span()
+        });
+    }
+
+    fn push_break(&mut self) {
+        self.spans.push(FormattedSpan {
+            index: self.spans.len(),
+            is_break: true,
+            ..FormattedSpan::default()
+        });
+        // Any more lines of the item carry on from its first
+        if let Some(list_item) = self.list_item.as_mut() {
+            list_item.is_continuation = true;
+        }
+    }
+
+    /// An empty span in the list item, quote and code block being written
+    fn new_span(&self) -> FormattedSpan {
+        FormattedSpan {
+            index: self.spans.len(),
+            list_item: self.list_item,
+            quote_depth: self.quote_depth,
+            is_code_block: self.is_code_block,
+            ..FormattedSpan::default()
+        }
+    }
+}
@@ -10,6 +10,57 @@ pub struct FormattedSpan {
     pub heading_level: usize,
     pub link: Option<String>,
     pub is_break: bool,
+    pub table: Option<TableData>,
+    #[serde(default)]
+    pub style: InlineStyle,
+    /// Set on every span of a line that is a list item
+    #[serde(default)]
+    pub list_item: Option<ListItem>,
+    /// How many block quotes the span is inside of
+    #[serde(default)]
+    pub quote_depth: usize,
+    /// Set on the lines of a code block, which keep their indentation
+    #[serde(default)]
+    pub is_code_block: bool,
+}
+
+/// Inline formatting of a span's text. Any of these can apply at once, eg. bold italic code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InlineStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub code: bool,
+    pub strikethrough: bool,
+}
+
+/// Where a line sits in a list. The bullet or number itself is drawn rather than kept in the text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ListItem {
+    /// 0 for a top level item, 1 for an item nested within it, and so on
+    pub depth: usize,
+    /// The item's number in an ordered list, `None` in a bulleted one
+    pub number: Option<usize>,
+    /// Whether the line carries on the item above it, eg. a second paragraph, so has no marker
+    pub is_continuation: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct TableData {
+    /// The first row is the header
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct InfoboxEntry {
+    pub label: String,
+    /// Empty for section headers within the infobox
+    pub value: String,
+}
+
+impl TableData {
+    pub fn column_count(&self) -> usize {
+        self.rows.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
 }
 
 impl Display for FormattedSpan {
@@ -26,8 +77,22 @@ impl Display for FormattedSpan {
                 "index: {}, text: {}, link: {}",
                 self.index, self.text, link_text
             )
+        } else if let Some(table) = &self.table {
+            write!(
+                f,
+                "index: {}, table: {} rows, {} columns",
+                self.index,
+                table.rows.len(),
+                table.column_count()
+            )
         } else if self.is_break {
             write!(f, "index: {}, line break", self.index)
+        } else if let Some(list_item) = &self.list_item {
+            write!(
+                f,
+                "index: {}, text: {}, list item at depth {}",
+                self.index, self.text, list_item.depth
+            )
         } else {
             write!(f, "index: {}, text: {}", self.index, self.text)
         }
@@ -43,6 +108,11 @@ impl Default for FormattedSpan {
             heading_level: 0,
             link: None,
             is_break: false,
+            table: None,
+            style: InlineStyle::default(),
+            list_item: None,
+            quote_depth: 0,
+            is_code_block: false,
         }
     }
 }
@@ -0,0 +1,174 @@
+use std::rc::Rc;
+
+use htmd::Element;
+use html5ever::{parse_document, tendril::TendrilSink};
+use markup5ever_rcdom::{Node, NodeData, RcDom};
+
+use crate::parsing::InfoboxEntry;
+
+// Tables that are page furniture rather than article content,
+// infoboxes are pulled out separately by `extract_infobox`
+const SKIPPED_TABLE_CLASSES: [&str; 5] = ["navbox", "ambox", "metadata", "sidebar", "infobox"];
+// Elements whose text never belongs in a table cell
+const SKIPPED_CELL_TAGS: [&str; 3] = ["sup", "style", "script"];
+
+/// htmd handler that turns a `<table>` into a Markdown pipe table, one line per row,
+/// with the first row as the header. Cell contents are flattened to plain text.
+pub fn table_handler(element: Element) -> Option<String> {
+    if has_any_class(element.node, &SKIPPED_TABLE_CLASSES) {
+        return None;
+    }
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    collect_rows(element.node, &mut rows);
+
+    let column_count = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    if column_count == 0 {
+        return None;
+    }
+
+    let mut markdown = String::from("\n\n");
+    if let Some(caption) = child_elements(element.node)
+        .find(|child| tag_name(child) == Some("caption"))
+        .map(|caption| cell_text(&caption))
+    {
+        if !caption.is_empty() {
+            markdown.push_str(&caption);
+            markdown.push_str("\n\n");
+        }
+    }
+
+    for (row_index, row) in rows.iter().enumerate() {
+        let mut cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        cells.resize(column_count, String::new());
+        markdown.push_str(&format!("| {} |\n", cells.join(" | ")));
+        if row_index == 0 {
+            markdown.push_str(&format!("|{}\n", " --- |".repeat(column_count)));
+        }
+    }
+    markdown.push('\n');
+
+    Some(markdown)
+}
+
+/// Pulls the label/value rows out of the first infobox table on the page.
+/// Rows with a single cell, like section headers, become entries with an empty value.
+pub fn extract_infobox(html: &str) -> Vec<InfoboxEntry> {
+    let dom = match parse_document(RcDom::default(), Default::default())
+        .from_utf8()
+        .read_from(&mut html.as_bytes())
+    {
+        Ok(dom) => dom,
+        Err(_) => return vec![],
+    };
+    let infobox = match find_table_with_class(&dom.document, "infobox") {
+        Some(infobox) => infobox,
+        None => return vec![],
+    };
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    collect_rows(&infobox, &mut rows);
+    rows.into_iter()
+        .filter_map(|row| {
+            let mut cells = row.into_iter().filter(|cell| !cell.is_empty());
+            let label = cells.next()?;
+            let value = cells.collect::<Vec<String>>().join(" ");
+            Some(InfoboxEntry { label, value })
+        })
+        .collect()
+}
+
+fn find_table_with_class(node: &Rc<Node>, class_name: &str) -> Option<Rc<Node>> {
+    if tag_name(node) == Some("table") && has_any_class(node, &[class_name]) {
+        return Some(Rc::clone(node));
+    }
+    child_elements(node).find_map(|child| find_table_with_class(&child, class_name))
+}
+
+fn collect_rows(node: &Rc<Node>, rows: &mut Vec<Vec<String>>) {
+    for child in child_elements(node) {
+        match tag_name(&child) {
+            Some("tr") => {
+                let mut row = Vec::new();
+                for cell in child_elements(&child) {
+                    if matches!(tag_name(&cell), Some("th") | Some("td")) {
+                        row.push(cell_text(&cell));
+                        // Pad out merged cells so the columns still line up
+                        for _ in 1..attribute_value(&cell, "colspan")
+                            .and_then(|colspan| colspan.parse::<usize>().ok())
+                            .unwrap_or(1)
+                        {
+                            row.push(String::new());
+                        }
+                    }
+                }
+                if !row.is_empty() {
+                    rows.push(row);
+                }
+            }
+            Some("thead") | Some("tbody") | Some("tfoot") => collect_rows(&child, rows),
+            _ => {}
+        }
+    }
+}
+
+fn cell_text(node: &Rc<Node>) -> String {
+    let mut text = String::new();
+    push_text(node, &mut text);
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn push_text(node: &Rc<Node>, text: &mut String) {
+    match &node.data {
+        NodeData::Text { contents } => text.push_str(&contents.borrow()),
+        NodeData::Element { name, .. } => {
+            if SKIPPED_CELL_TAGS.contains(&name.local.as_ref()) {
+                return;
+            }
+            if matches!(name.local.as_ref(), "br" | "p" | "div" | "li") {
+                text.push(' ');
+            }
+            for child in node.children.borrow().iter() {
+                push_text(child, text);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn child_elements(node: &Rc<Node>) -> impl Iterator<Item = Rc<Node>> {
+    node.children
+        .borrow()
+        .iter()
+        .filter(|child| matches!(child.data, NodeData::Element { .. }))
+        .cloned()
+        .collect::<Vec<Rc<Node>>>()
+        .into_iter()
+}
+
+fn tag_name(node: &Rc<Node>) -> Option<&str> {
+    match &node.data {
+        NodeData::Element { name, .. } => Some(name.local.as_ref()),
+        _ => None,
+    }
+}
+
+fn attribute_value(node: &Rc<Node>, attribute_name: &str) -> Option<String> {
+    match &node.data {
+        NodeData::Element { attrs, .. } => attrs
+            .borrow()
+            .iter()
+            .find(|attr| attr.name.local.as_ref() == attribute_name)
+            .map(|attr| attr.value.to_string()),
+        _ => None,
+    }
+}
+
+fn has_any_class(node: &Rc<Node>, class_names: &[&str]) -> bool {
+    match attribute_value(node, "class") {
+        Some(classes) => classes
+            .split_whitespace()
+            .any(|class| class_names.contains(&class)),
+        None => false,
+    }
+}
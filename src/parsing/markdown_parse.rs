@@ -1,114 +1,70 @@
-use crate::{parsing::FormattedSpan, utils::cut_off_from_char};
-use regex::Regex;
+use htmd::Element;
 
-pub fn parse_markdown(text: &str) -> Vec<FormattedSpan> {
-    let mut spans: Vec<FormattedSpan> = Vec::new();
-
-    let heading_regex = Regex::new("^(?<hashes>#{1,6})\\s+(?<text>.*)").unwrap();
-    let link_regex =
-        Regex::new(r#"\[(?P<text>[^\]]+)\]\((?P<link>(\\\)|\"[^\"]+\"|[^\)])+)\)"#).unwrap();
-    let image_regex = Regex::new("^\\[\\!\\[").unwrap();
-
-    let mut index = 0;
-
-    for line in text.lines() {
-        // check if the entire line is a heading, this is the only way a header should exist
-        if let Some(captures) = heading_regex.captures(line) {
-            if let Some(text_match) = captures.name("text") {
-                if let Some(hashes) = captures.name("hashes") {
-                    spans.push(FormattedSpan {
-                        index: index,
-                        text: text_match.as_str().to_owned(),
-                        is_heading: true,
-                        heading_level: hashes.len(),
-                        link: None,
-                        is_break: false,
-                    });
-                    index += 1;
-                }
-            }
-        } else if let Some(_) = image_regex.captures(line) {
-            continue;
-        } else {
-            let line_content = line.to_string();
-            let mut current_pos: usize = 0;
-
-            for link_capture in link_regex.captures_iter(line) {
-                let start_pos = match link_capture.get(0) {
-                    Some(link_match) => link_match.start(),
-                    None => current_pos,
-                };
-
-                let end_pos = match link_capture.get(0) {
-                    Some(link_match) => link_match.end(),
-                    None => current_pos,
-                };
-
-                let text_part = match link_capture.name("text") {
-                    Some(link_match) => link_match.as_str().to_string(),
-                    None => String::from(""),
-                };
-
-                let mut link_part = match link_capture.name("link") {
-                    Some(link_match) => link_match.as_str().to_string(),
-                    None => String::from(""),
-                };
+use crate::parsing::{Document, FormattedSpan};
 
-                link_part = cut_off_from_char(cut_off_from_char(&link_part, '\"'), '#')
-                    .replace("\\)", ")")
-                    .replace("\\(", "(")
-                    .to_string();
+/// Parses the Markdown htmd makes of a page into a `Document`, and flattens that into spans
+pub fn parse_markdown(text: &str) -> Vec<FormattedSpan> {
+    Document::from_markdown(text).spans()
+}
 
-                // Text before the link
-                if current_pos < start_pos {
-                    let pre_link_text = line_content[current_pos..start_pos].to_string();
-                    spans.push(FormattedSpan {
-                        index: index,
-                        text: pre_link_text,
-                        is_heading: false,
-                        heading_level: 0,
-                        link: None,
-                        is_break: false,
-                    });
-                    index += 1;
-                }
-                // link snippet
-                spans.push(FormattedSpan {
-                    index: index,
-                    text: text_part,
-                    is_heading: false,
-                    heading_level: 0,
-                    link: Some(link_part),
-                    is_break: false,
-                });
-                index += 1;
+/// htmd handler that wraps struck-out text in `~~`, which `parse_markdown` reads as strikethrough
+pub fn strikethrough_handler(element: Element) -> Option<String> {
+    let content = element.content.trim();
+    match content.is_empty() {
+        true => None,
+        false => Some(format!("~~{content}~~")),
+    }
+}
 
-                current_pos = end_pos;
-            }
+/// htmd handler that fences preformatted text as a code block, which htmd only does for `<pre><code>`
+pub fn preformatted_handler(element: Element) -> Option<String> {
+    let content = element.content.trim_matches('\n');
+    if content.trim().is_empty() {
+        return None;
+    }
+    if content.starts_with("```") {
+        return Some(format!("\n\n{content}\n\n"));
+    }
+    // htmd escapes a backtick or tilde at the very start
+    let content = match content.strip_prefix('\\') {
+        Some(unescaped) if unescaped.starts_with(['`', '~']) => unescaped,
+        _ => content,
+    };
+    let longest_backtick_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_backtick_run.max(2) + 1);
+    Some(format!("\n\n{fence}\n{content}\n{fence}\n\n"))
+}
 
-            // Any remaining text
-            if current_pos < line_content.len() {
-                spans.push(FormattedSpan {
-                    index: index,
-                    text: line_content[current_pos..].to_string(),
-                    is_heading: false,
-                    heading_level: 0,
-                    link: None,
-                    is_break: false,
-                });
-                index += 1;
-            }
+/// Indices of the first span of every link. A link whose text changes formatting part way
+/// through is split over several spans in a row with the same target.
+pub fn link_span_indices(spans: &[FormattedSpan]) -> Vec<usize> {
+    let mut previous_link: Option<&String> = None;
+    let mut indices = Vec::new();
+    for formatted_span in spans {
+        if formatted_span.link.is_some() && formatted_span.link.as_ref() != previous_link {
+            indices.push(formatted_span.index);
         }
-        spans.push(FormattedSpan {
-            index,
-            text: String::from(""),
-            is_heading: false,
-            heading_level: 0,
-            link: None,
-            is_break: true,
-        });
-        index += 1;
+        previous_link = formatted_span.link.as_ref();
     }
+    indices
+}
 
-    spans
+/// Indices of every span of the link starting at `first_span_index`
+pub fn link_span_group(spans: &[FormattedSpan], first_span_index: usize) -> Vec<usize> {
+    let first_position = match spans
+        .iter()
+        .position(|formatted_span| formatted_span.index == first_span_index)
+    {
+        Some(first_position) => first_position,
+        None => return vec![],
+    };
+    let link = match &spans[first_position].link {
+        Some(link) => link,
+        None => return vec![],
+    };
+    spans[first_position..]
+        .iter()
+        .take_while(|formatted_span| formatted_span.link.as_ref() == Some(link))
+        .map(|formatted_span| formatted_span.index)
+        .collect()
 }
@@ -1,4 +1,10 @@
+pub mod document;
 pub mod formatted_span;
+pub mod html_table;
 pub mod markdown_parse;
-pub use formatted_span::FormattedSpan;
-pub use markdown_parse::parse_markdown;
+pub use document::Document;
+pub use formatted_span::{FormattedSpan, InfoboxEntry, InlineStyle, ListItem, TableData};
+pub use html_table::{extract_infobox, table_handler};
+pub use markdown_parse::{
+    link_span_group, link_span_indices, parse_markdown, preformatted_handler, strikethrough_handler,
+};
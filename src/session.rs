@@ -0,0 +1,76 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader},
+    path::PathBuf,
+};
+
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{AppState, ArticleView, HistoryPage};
+use crate::wikipedia::{SearchResult, WikiSource};
+
+const SESSION_FILE: &str = ".config/wik/session.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TabSession {
+    pub title: String,
+    /// Wiki the tab's article comes from, or the session's wiki if not given
+    #[serde(default)]
+    pub wiki: Option<WikiSource>,
+    #[serde(default)]
+    pub view: ArticleView,
+    /// Pages gone through to get here, oldest first and ending with this one
+    #[serde(default)]
+    pub back_history: Vec<HistoryPage>,
+    #[serde(default)]
+    pub forward_history: Vec<HistoryPage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchSession {
+    pub query: String,
+    pub results: Vec<SearchResult>,
+    pub selected_index: usize,
+}
+
+/// What was open when wik last quit, saved to `~/.config/wik/session.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Screen that was open
+    pub state: Option<AppState>,
+    pub wiki: Option<WikiSource>,
+    pub tabs: Vec<TabSession>,
+    pub active_tab: usize,
+    /// Name of the theme in use
+    pub theme: Option<String>,
+    /// Last search made, with its results
+    pub search: Option<SearchSession>,
+}
+
+impl Session {
+    fn file_path() -> Option<PathBuf> {
+        home_dir().map(|home_dir_path| home_dir_path.join(SESSION_FILE))
+    }
+
+    pub fn load() -> Option<Self> {
+        let file_path = Self::file_path()?;
+        let file = File::options()
+            .read(true)
+            .write(false)
+            .open(file_path)
+            .ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(file_path) = Self::file_path() {
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(file_path, serde_json::to_string_pretty(self)?)?;
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,100 @@
-use serde::{Deserialize, Serialize};
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use dirs::{data_dir, home_dir};
 use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{hex_to_rgb, nearest_ansi16_color, nearest_ansi256_color};
+
+/// Themes that come with wik, one `Name hex-hex-hex-hex-hex-hex` per line
+const BUILTIN_THEMES: &str = include_str!("../themes.txt");
+const CONFIG_THEMES_DIR: &str = ".config/wik/themes";
 
-use crate::utils::hex_to_rgb;
+/// How many colours the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+pub enum ColorSupport {
+    /// Any RGB colour
+    #[default]
+    #[value(name = "truecolor")]
+    TrueColor,
+    /// The 256-colour palette
+    #[value(name = "256")]
+    Ansi256,
+    /// The 16 named colours
+    #[value(name = "16")]
+    Ansi16,
+    /// No colour at all, only bold, underlined and reversed text
+    #[value(name = "never")]
+    Monochrome,
+}
+
+impl ColorSupport {
+    /// Works out what the terminal can show from `NO_COLOR`, `COLORTERM` and `TERM`
+    pub fn detect() -> ColorSupport {
+        if env::var_os("NO_COLOR").is_some_and(|no_color| !no_color.is_empty()) {
+            return ColorSupport::Monochrome;
+        }
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorSupport::TrueColor;
+        }
+        match env::var("TERM") {
+            Ok(term) => {
+                let term = term.to_lowercase();
+                if term == "dumb" || term.starts_with("vt") {
+                    ColorSupport::Monochrome
+                } else if term.contains("direct") || term.contains("truecolor") {
+                    ColorSupport::TrueColor
+                } else if term.contains("256") {
+                    ColorSupport::Ansi256
+                } else {
+                    ColorSupport::Ansi16
+                }
+            }
+            // Windows terminals don't set TERM, and show RGB colours
+            Err(_) if cfg!(windows) => ColorSupport::TrueColor,
+            Err(_) => ColorSupport::Ansi16,
+        }
+    }
+
+    /// The colour the terminal can show that is closest to `color`
+    pub fn adapt_color(&self, color: Color) -> Color {
+        match self {
+            ColorSupport::TrueColor => color,
+            ColorSupport::Ansi256 => nearest_ansi256_color(color),
+            ColorSupport::Ansi16 => nearest_ansi16_color(color),
+            ColorSupport::Monochrome => Color::Reset,
+        }
+    }
+}
+
+const BASE_COLOR_FIELDS: [&str; 6] = [
+    "background",
+    "text",
+    "secondary",
+    "tertiary",
+    "highlight",
+    "negative_text",
+];
+
+const STYLE_SLOT_FIELDS: [&str; 12] = [
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "link",
+    "selected_link",
+    "visited_link",
+    "border",
+    "scrollbar",
+    "search_match",
+];
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Theme {
@@ -12,26 +105,174 @@ pub struct Theme {
     pub tertiary: Color,
     pub highlight: Color,
     pub negative_text: Color,
+    /// Headings from level 1 to 6
+    pub headings: [Style; 6],
+    pub link: Style,
+    pub selected_link: Style,
+    /// Links to articles found in the history
+    pub visited_link: Style,
+    /// Borders and text of the focused block, over the background colour
+    pub border: Style,
+    /// Handle of the scrollbar
+    pub scrollbar: Style,
+    pub search_match: Style,
+    /// What the terminal can show, see `for_color_support`
+    #[serde(skip)]
+    pub color_support: ColorSupport,
 }
 
 impl Default for Theme {
     fn default() -> Self {
-        Self {
-            // 2a3138-ffffff-c19c00-13a10e-3b78ff-000000
-            name: String::from("Default"),
-            background: Color::Rgb(42, 49, 56),
-            text: Color::White,
-            secondary: Color::Yellow,
-            tertiary: Color::Green,
-            highlight: Color::LightBlue,
-            negative_text: Color::Black,
+        // 2a3138-ffffff-c19c00-13a10e-3b78ff-000000
+        Theme::from_colors(
+            String::from("Default"),
+            [
+                Color::Rgb(42, 49, 56),
+                Color::White,
+                Color::Yellow,
+                Color::Green,
+                Color::LightBlue,
+                Color::Black,
+            ],
+        )
+    }
+}
+
+/// A problem found in a theme, pointing at where it is when that is known
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThemeError {
+    pub line: Option<usize>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ThemeError {
+    fn new(line: Option<usize>, field: Option<&str>, message: String) -> Self {
+        ThemeError {
+            line,
+            field: field.map(String::from),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, &self.field) {
+            (Some(line), Some(field)) => write!(f, "line {line}, field \"{field}\": ")?,
+            (Some(line), None) => write!(f, "line {line}: ")?,
+            (None, Some(field)) => write!(f, "field \"{field}\": ")?,
+            (None, None) => {}
         }
+        write!(f, "{}", self.message)
     }
 }
 
 impl Theme {
+    /// A theme from its six base colours, in the order of `BASE_COLOR_FIELDS`,
+    /// with every style slot worked out from them
+    pub fn from_colors(name: String, colors: [Color; 6]) -> Theme {
+        let [background, text, secondary, tertiary, highlight, negative_text] = colors;
+        let major_heading = Style::default()
+            .add_modifier(Modifier::BOLD)
+            .add_modifier(Modifier::ITALIC);
+        let minor_heading = Style::default().add_modifier(Modifier::BOLD);
+        Theme {
+            name,
+            background,
+            text,
+            secondary,
+            tertiary,
+            highlight,
+            negative_text,
+            headings: [
+                major_heading,
+                major_heading,
+                minor_heading,
+                minor_heading,
+                minor_heading,
+                minor_heading,
+            ],
+            link: Style::default().fg(text).add_modifier(Modifier::UNDERLINED),
+            selected_link: Style::default()
+                .bg(highlight)
+                .fg(negative_text)
+                .add_modifier(Modifier::UNDERLINED),
+            visited_link: Style::default()
+                .fg(tertiary)
+                .add_modifier(Modifier::UNDERLINED),
+            border: Style::default().fg(secondary),
+            scrollbar: Style::default().fg(tertiary),
+            search_match: Style::default().bg(tertiary).fg(negative_text),
+            color_support: ColorSupport::TrueColor,
+        }
+    }
+
+    pub fn heading_style(&self, heading_level: usize) -> Style {
+        self.headings[heading_level.clamp(1, 6) - 1]
+    }
+
+    pub fn link_style(&self, is_selected: bool, is_visited: bool) -> Style {
+        match (is_selected, is_visited) {
+            (true, _) => self.selected_link,
+            (false, true) => self.visited_link,
+            (false, false) => self.link,
+        }
+    }
+
+    pub fn scrollbar_track_style(&self) -> Style {
+        Style::default().fg(self.secondary)
+    }
+
+    pub fn scrollbar_handle_style(&self) -> Style {
+        self.scrollbar
+    }
+
+    /// The theme made to suit what the terminal can show. Monochrome themes have no colours,
+    /// so links and selections are told apart by underlined and reversed text instead.
+    /// Any other colour on screen is brought within range by `ColorFilter` as it's drawn.
+    pub fn for_color_support(&self, color_support: ColorSupport) -> Theme {
+        match color_support {
+            ColorSupport::Monochrome => {
+                let mut theme = Theme::from_colors(self.name.clone(), [Color::Reset; 6]);
+                theme.headings = self
+                    .headings
+                    .map(|heading| Style::default().add_modifier(heading.add_modifier));
+                theme.link = Style::default().add_modifier(Modifier::UNDERLINED);
+                theme.selected_link = Style::default()
+                    .add_modifier(Modifier::UNDERLINED)
+                    .add_modifier(Modifier::REVERSED);
+                theme.visited_link = Style::default()
+                    .add_modifier(Modifier::UNDERLINED)
+                    .add_modifier(Modifier::DIM);
+                theme.border = Style::default();
+                theme.scrollbar = Style::default();
+                theme.search_match = Style::default().add_modifier(Modifier::REVERSED);
+                theme.color_support = color_support;
+                theme
+            }
+            _ => Theme {
+                color_support,
+                ..self.clone()
+            },
+        }
+    }
+
+    /// Styles that stand out by their background colour are reversed instead on monochrome terminals
+    fn stand_out(&self, style: Style) -> Style {
+        match self.color_support {
+            ColorSupport::Monochrome => style.add_modifier(Modifier::REVERSED),
+            _ => style,
+        }
+    }
+
     pub fn highlighted_snippet_style(&self) -> Style {
-        Style::default().bg(self.highlight).fg(self.negative_text)
+        self.stand_out(Style::default().bg(self.highlight).fg(self.negative_text))
+    }
+
+    /// `code` within article text
+    pub fn inline_code_style(&self) -> Style {
+        self.stand_out(Style::default().fg(self.tertiary))
     }
 
     pub fn unhighlighted_snippet_style(&self) -> Style {
@@ -39,15 +280,17 @@ impl Theme {
     }
 
     pub fn cursor_style(&self) -> Style {
-        Style::default().bg(self.secondary).fg(self.negative_text)
+        self.stand_out(Style::default().bg(self.secondary).fg(self.negative_text))
     }
 
     pub fn highlighted_title_style(&self) -> Style {
-        Style::default()
-            .bg(self.secondary)
-            .fg(self.negative_text)
-            .add_modifier(Modifier::UNDERLINED)
-            .add_modifier(Modifier::BOLD)
+        self.stand_out(
+            Style::default()
+                .bg(self.secondary)
+                .fg(self.negative_text)
+                .add_modifier(Modifier::UNDERLINED)
+                .add_modifier(Modifier::BOLD),
+        )
     }
 
     pub fn unhighlighted_title_style(&self) -> Style {
@@ -76,41 +319,490 @@ impl Theme {
             .add_modifier(Modifier::ITALIC)
     }
 
+    pub fn search_match_style(&self) -> Style {
+        self.search_match
+    }
+
+    pub fn current_search_match_style(&self) -> Style {
+        self.stand_out(
+            Style::default()
+                .bg(self.secondary)
+                .fg(self.negative_text)
+                .add_modifier(Modifier::BOLD),
+        )
+    }
+
+    pub fn link_hint_style(&self) -> Style {
+        self.stand_out(
+            Style::default()
+                .bg(self.secondary)
+                .fg(self.negative_text)
+                .add_modifier(Modifier::BOLD),
+        )
+    }
+
     pub fn block_border_unfocus(&self) -> Style {
         Style::default().fg(self.text).bg(self.background)
     }
 
     pub fn block_border_focus(&self) -> Style {
-        Style::default().fg(self.secondary).bg(self.background)
+        Style::default().bg(self.background).patch(self.border)
     }
 
     // pub fn title_
 
     pub fn from_hex_string_series(name: String, hex_string_series: String) -> Theme {
-        let theme_colors: Vec<Color> = hex_string_series
+        Theme::parse_hex_string_series(name, &hex_string_series).unwrap_or_default()
+    }
+
+    /// Reads the six base colours from a dash-separated hex series like `2a3138-ffffff-...`
+    pub fn parse_hex_string_series(name: String, hex_string_series: &str) -> Result<Theme, String> {
+        let theme_colors = hex_string_series
             .split('-')
-            .map(|hex_string| -> Option<Color> {
-                if let Ok(color) = hex_to_rgb(hex_string) {
-                    Some(color)
-                } else {
-                    None
-                }
+            .map(|hex_string| {
+                hex_to_rgb(hex_string)
+                    .map_err(|e| format!("\"{hex_string}\" is not a colour ({e})"))
+            })
+            .collect::<Result<Vec<Color>, String>>()?;
+        match theme_colors.get(..6) {
+            Some(&[background, text, secondary, tertiary, highlight, negative_text]) => {
+                Ok(Theme::from_colors(
+                    name,
+                    [
+                        background,
+                        text,
+                        secondary,
+                        tertiary,
+                        highlight,
+                        negative_text,
+                    ],
+                ))
+            }
+            _ => Err(format!("expected 6 colours, found {}", theme_colors.len())),
+        }
+    }
+
+    /// Reads a theme from a TOML file's text, named `default_name` unless it has a `name`
+    pub fn from_toml(default_name: &str, text: &str) -> Result<Theme, ThemeError> {
+        let table = text.parse::<toml::Table>().map_err(|e| {
+            let line = e.span().map(|span| line_of_offset(text, span.start));
+            ThemeError::new(line, None, e.message().replace('\n', ", "))
+        })?;
+        let fields = table
+            .into_iter()
+            .map(|(field, value)| match value {
+                toml::Value::String(value) => Ok((field, value)),
+                _ => Err(ThemeError::new(
+                    line_of_field(text, &field),
+                    Some(&field),
+                    String::from("expected a string"),
+                )),
             })
-            .filter(|maybe_color| maybe_color.is_some())
-            .map(|some_color| some_color.expect("Color parsing error"))
-            .collect();
-
-        if theme_colors.len() >= 6 {
-            return Theme {
-                name: name,
-                background: theme_colors[0],
-                text: theme_colors[1],
-                secondary: theme_colors[2],
-                tertiary: theme_colors[3],
-                highlight: theme_colors[4],
-                negative_text: theme_colors[5],
+            .collect::<Result<Vec<(String, String)>, ThemeError>>()?;
+        Theme::from_fields(default_name, text, fields)
+    }
+
+    /// Reads a theme from a JSON file's text, named `default_name` unless it has a `name`
+    pub fn from_json(default_name: &str, text: &str) -> Result<Theme, ThemeError> {
+        let object = serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(text)
+            .map_err(|e| ThemeError::new(Some(e.line()), None, e.to_string()))?;
+        let fields = object
+            .into_iter()
+            .map(|(field, value)| match value {
+                serde_json::Value::String(value) => Ok((field, value)),
+                _ => Err(ThemeError::new(
+                    line_of_field(text, &field),
+                    Some(&field),
+                    String::from("expected a string"),
+                )),
+            })
+            .collect::<Result<Vec<(String, String)>, ThemeError>>()?;
+        Theme::from_fields(default_name, text, fields)
+    }
+
+    /// Builds a theme from `field = value` pairs: a `name`, the base colours, then style slots
+    /// (`h1` to `h6`, `link`, `selected_link`, `visited_link`, `border`, `scrollbar`
+    /// and `search_match`) written like `bold underline #ffb0b2 on black`
+    fn from_fields(
+        default_name: &str,
+        text: &str,
+        fields: Vec<(String, String)>,
+    ) -> Result<Theme, ThemeError> {
+        let field_error = |field: &str, message: String| {
+            ThemeError::new(line_of_field(text, field), Some(field), message)
+        };
+
+        let default_theme = Theme::default();
+        let mut name = default_name.to_string();
+        let mut colors = [
+            default_theme.background,
+            default_theme.text,
+            default_theme.secondary,
+            default_theme.tertiary,
+            default_theme.highlight,
+            default_theme.negative_text,
+        ];
+        for (field, value) in fields.iter() {
+            if field == "name" {
+                name = value.clone();
+            } else if let Some(color_index) = BASE_COLOR_FIELDS
+                .iter()
+                .position(|base_field| base_field == field)
+            {
+                colors[color_index] = parse_color(value).map_err(|e| field_error(field, e))?;
+            }
+        }
+
+        let mut theme = Theme::from_colors(name, colors);
+        for (field, value) in fields.iter() {
+            if field == "name" || BASE_COLOR_FIELDS.contains(&field.as_str()) {
+                continue;
+            }
+            let slot_index = STYLE_SLOT_FIELDS
+                .iter()
+                .position(|slot_field| slot_field == field)
+                .ok_or_else(|| field_error(field, String::from("not a theme field")))?;
+            let style = parse_style(value).map_err(|e| field_error(field, e))?;
+            *theme.style_slots_mut()[slot_index] = style;
+        }
+        Ok(theme)
+    }
+
+    /// The base colours in the order of `BASE_COLOR_FIELDS`
+    pub fn base_colors(&self) -> [Color; 6] {
+        [
+            self.background,
+            self.text,
+            self.secondary,
+            self.tertiary,
+            self.highlight,
+            self.negative_text,
+        ]
+    }
+
+    /// The style slots in the order of `STYLE_SLOT_FIELDS`
+    pub fn style_slots(&self) -> [Style; 12] {
+        let [h1, h2, h3, h4, h5, h6] = self.headings;
+        [
+            h1,
+            h2,
+            h3,
+            h4,
+            h5,
+            h6,
+            self.link,
+            self.selected_link,
+            self.visited_link,
+            self.border,
+            self.scrollbar,
+            self.search_match,
+        ]
+    }
+
+    pub fn style_slots_mut(&mut self) -> [&mut Style; 12] {
+        let [h1, h2, h3, h4, h5, h6] = &mut self.headings;
+        [
+            h1,
+            h2,
+            h3,
+            h4,
+            h5,
+            h6,
+            &mut self.link,
+            &mut self.selected_link,
+            &mut self.visited_link,
+            &mut self.border,
+            &mut self.scrollbar,
+            &mut self.search_match,
+        ]
+    }
+
+    /// This theme with other base colours. Style slots that were worked out from the old
+    /// colours are worked out again from the new ones, and any set by hand are kept.
+    pub fn with_colors(&self, colors: [Color; 6]) -> Theme {
+        let derived_theme = Theme::from_colors(self.name.clone(), self.base_colors());
+        let mut theme = Theme::from_colors(self.name.clone(), colors);
+        let own_styles = self.style_slots();
+        let derived_styles = derived_theme.style_slots();
+        for (slot_index, slot) in theme.style_slots_mut().into_iter().enumerate() {
+            if own_styles[slot_index] != derived_styles[slot_index] {
+                *slot = own_styles[slot_index];
+            }
+        }
+        theme
+    }
+
+    /// The theme in the TOML theme format, leaving out the style slots that
+    /// are the same as the ones worked out from its base colours
+    pub fn to_toml(&self) -> String {
+        let derived_theme = Theme::from_colors(self.name.clone(), self.base_colors());
+        let mut lines = vec![format!("name = {}", toml::Value::String(self.name.clone()))];
+        for (field, color) in BASE_COLOR_FIELDS.iter().zip(self.base_colors()) {
+            lines.push(format!("{field} = \"{}\"", color_to_string(color)));
+        }
+        let derived_styles = derived_theme.style_slots();
+        for (slot_index, style) in self.style_slots().into_iter().enumerate() {
+            if style != derived_styles[slot_index] {
+                lines.push(format!(
+                    "{} = \"{}\"",
+                    STYLE_SLOT_FIELDS[slot_index],
+                    style_to_string(style)
+                ));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Writes the theme to a TOML file named after it in `~/.config/wik/themes`,
+    /// returning the file's path. A file there for a theme with another name is left alone.
+    pub fn save(&self) -> io::Result<PathBuf> {
+        let themes_dir = home_dir()
+            .ok_or(io::Error::new(
+                io::ErrorKind::NotFound,
+                "could not find the home directory",
+            ))?
+            .join(CONFIG_THEMES_DIR);
+        let file_stem = self
+            .name
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect::<Vec<String>>()
+            .join("-");
+        if file_stem.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the name needs a letter or digit",
+            ));
+        }
+        let file_path = themes_dir.join(format!("{file_stem}.toml"));
+        if let Ok(existing_text) = fs::read_to_string(&file_path) {
+            let existing_name = Theme::from_toml(&file_stem, &existing_text)
+                .map(|existing_theme| existing_theme.name)
+                .unwrap_or_default();
+            if existing_name != self.name {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} belongs to another theme", file_path.display()),
+                ));
+            }
+        }
+        fs::create_dir_all(&themes_dir)?;
+        fs::write(&file_path, self.to_toml())?;
+        Ok(file_path)
+    }
+}
+
+/// A colour written the way `parse_color` reads it
+pub fn color_to_string(color: Color) -> String {
+    color.to_string().to_lowercase()
+}
+
+/// A style written the way `parse_style` reads it
+pub fn style_to_string(style: Style) -> String {
+    let mut words = vec![];
+    for (modifier, word) in [
+        (Modifier::BOLD, "bold"),
+        (Modifier::DIM, "dim"),
+        (Modifier::ITALIC, "italic"),
+        (Modifier::UNDERLINED, "underline"),
+        (Modifier::REVERSED, "reversed"),
+        (Modifier::CROSSED_OUT, "strikethrough"),
+    ] {
+        if style.add_modifier.contains(modifier) {
+            words.push(word.to_string());
+        }
+    }
+    if let Some(foreground) = style.fg {
+        words.push(color_to_string(foreground));
+    }
+    if let Some(background) = style.bg {
+        words.push(format!("on {}", color_to_string(background)));
+    }
+    words.join(" ")
+}
+
+/// Reads a colour typed into the theme editor: anything `parse_color` reads,
+/// a hex code without its `#`, or red, green and blue values like `255, 176, 178`
+pub fn parse_color_input(text: &str) -> Result<Color, String> {
+    let text = text.trim();
+    let components: Vec<&str> = text
+        .split([',', ' '])
+        .filter(|component| !component.is_empty())
+        .collect();
+    if components.len() == 3 && components[0].starts_with(|c: char| c.is_ascii_digit()) {
+        let values = components
+            .iter()
+            .map(|component| component.parse::<u8>())
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| String::from("red, green and blue go from 0 to 255"))?;
+        return Ok(Color::Rgb(values[0], values[1], values[2]));
+    }
+    if text.len() == 6 && text.chars().all(|c| c.is_ascii_hexdigit()) {
+        return hex_to_rgb(text);
+    }
+    parse_color(text)
+}
+
+/// Reads a colour name like `red` or `lightblue`, or a hex code like `#ffb0b2`
+pub fn parse_color(text: &str) -> Result<Color, String> {
+    if text.starts_with('#') {
+        return hex_to_rgb(text).map_err(|e| format!("\"{text}\" is not a colour ({e})"));
+    }
+    text.parse::<Color>()
+        .map_err(|_| format!("\"{text}\" is not a colour"))
+}
+
+/// Reads a style written as modifiers and colours, eg. `bold italic #ffb0b2 on black`.
+/// The colour after `on` is the background.
+pub fn parse_style(text: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        let modifier = match word.to_lowercase().as_str() {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underline" | "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            "strikethrough" | "crossed_out" => Modifier::CROSSED_OUT,
+            "on" => {
+                let background = words
+                    .next()
+                    .ok_or(String::from("expected a colour after \"on\""))?;
+                style = style.bg(parse_color(background)?);
+                continue;
+            }
+            _ => {
+                style = style.fg(parse_color(word)?);
+                continue;
+            }
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+fn line_of_offset(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// The line a field is set on, for pointing at mistakes in theme files
+fn line_of_field(text: &str, field: &str) -> Option<usize> {
+    text.lines()
+        .position(|line| {
+            let line = line
+                .trim_start()
+                .trim_start_matches(['{', ','])
+                .trim_start();
+            let rest = match line.strip_prefix('"') {
+                Some(quoted) => quoted
+                    .strip_prefix(field)
+                    .and_then(|rest| rest.strip_prefix('"')),
+                None => line.strip_prefix(field),
             };
+            rest.is_some_and(|rest| rest.trim_start().starts_with(['=', ':']))
+        })
+        .map(|line_index| line_index + 1)
+}
+
+/// Reads themes written one per line as a name followed by a dash-separated hex series
+pub fn parse_theme_list(text: &str) -> (Vec<Theme>, Vec<ThemeError>) {
+    let mut themes = vec![];
+    let mut errors = vec![];
+    for (line_index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (name, hex_string_series) = match line.trim().split_once(' ') {
+            Some(theme_parts) => theme_parts,
+            None => {
+                errors.push(ThemeError::new(
+                    Some(line_index + 1),
+                    None,
+                    String::from("expected a name followed by colours"),
+                ));
+                continue;
+            }
+        };
+        match Theme::parse_hex_string_series(name.to_string(), hex_string_series.trim()) {
+            Ok(theme) => themes.push(theme),
+            Err(e) => errors.push(ThemeError::new(Some(line_index + 1), Some(name), e)),
+        }
+    }
+    (themes, errors)
+}
+
+/// Where users keep their own themes, least important first:
+/// the XDG data directory, then `~/.config/wik/themes`
+pub fn theme_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(data_dir_path) = data_dir() {
+        dirs.push(data_dir_path.join("wik").join("themes"));
+    }
+    if let Some(home_dir_path) = home_dir() {
+        dirs.push(home_dir_path.join(CONFIG_THEMES_DIR));
+    }
+    dirs
+}
+
+/// Reads the themes in a file: a list of hex series for `.txt`, or one theme for `.toml`/`.json`
+pub fn load_theme_file(path: &Path) -> (Vec<Theme>, Vec<ThemeError>) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => return (vec![], vec![ThemeError::new(None, None, e.to_string())]),
+    };
+    let default_name = path
+        .file_stem()
+        .map(|file_stem| file_stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let theme_result = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => Theme::from_toml(&default_name, &text),
+        Some("json") => Theme::from_json(&default_name, &text),
+        _ => return parse_theme_list(&text),
+    };
+    match theme_result {
+        Ok(theme) => (vec![theme], vec![]),
+        Err(e) => (vec![], vec![e]),
+    }
+}
+
+/// The built-in themes followed by any found in the theme directories, along with
+/// what was wrong with any that could not be read.
+/// A theme with the same name as an earlier one takes its place.
+pub fn load_themes() -> (Vec<Theme>, Vec<String>) {
+    let (mut themes, _) = parse_theme_list(BUILTIN_THEMES);
+    let mut errors = vec![];
+    for dir in theme_dirs() {
+        let mut theme_files: Vec<PathBuf> = match fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension().is_some_and(|extension| {
+                        extension == "txt" || extension == "toml" || extension == "json"
+                    })
+                })
+                .collect(),
+            Err(_) => continue,
+        };
+        theme_files.sort();
+        for theme_file in theme_files {
+            let (file_themes, file_errors) = load_theme_file(&theme_file);
+            errors.extend(
+                file_errors
+                    .iter()
+                    .map(|e| format!("{}: {}", theme_file.display(), e)),
+            );
+            for theme in file_themes {
+                match themes
+                    .iter()
+                    .position(|existing| existing.name == theme.name)
+                {
+                    Some(theme_index) => themes[theme_index] = theme,
+                    None => themes.push(theme),
+                }
+            }
         }
-        Theme::default()
     }
+    (themes, errors)
 }
@@ -1,11 +1,15 @@
-use std::ops::Deref;
+use std::collections::{HashMap, HashSet};
 use std::sync::{MutexGuard, TryLockError, TryLockResult};
 
-use crate::app::{ActionItem, ActionMenu, App, AppState, MenuState, TypeableState};
-use crate::parsing::FormattedSpan;
+use crate::app::{
+    link_target_title, ActionItem, ActionMenu, App, AppState, ArticleState, MenuState, TextMatch,
+    TypeableState, FIRST_STYLE_SLOT_FIELD, THEME_EDITOR_FIELDS,
+};
+use crate::article_layout::FragmentKind;
+use crate::parsing::{link_span_group, FormattedSpan, InfoboxEntry, InlineStyle};
 use crate::styles::Theme;
-use crate::utils::{wrapped_iter_enumerate, WIK_TITLE};
-use crate::widgets::{AlphaBox, Eraser, ScrollBar, TextBox};
+use crate::utils::{format_timestamp, wrapped_iter_enumerate, WIK_TITLE};
+use crate::widgets::{AlphaBox, ColorFilter, Eraser, ScrollBar, TextBox};
 use crate::wikipedia::SearchResult;
 use ratatui::layout::Rect;
 use ratatui::style::{Color, Modifier};
@@ -15,7 +19,7 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::Style,
     text::Span,
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{Block, Borders, Paragraph, Tabs, Wrap},
     Frame,
 };
 
@@ -25,22 +29,215 @@ pub fn draw(frame: &mut Frame, app: &App) {
         Block::default().style(app.theme.window_background()),
         window_area,
     );
-    match app.state {
+    draw_state(frame, app, app.state);
+
+    // Render debug text
+    if !app.debug_text.is_empty() {
+        let debug_paragraph = Paragraph::new(app.debug_text.clone());
+        frame.render_widget(debug_paragraph, frame.area());
+    }
+
+    frame.render_widget(ColorFilter::new(app.color_support), frame.area());
+}
+
+fn draw_state(frame: &mut Frame, app: &App, state: AppState) {
+    match state {
         AppState::Title => draw_title(frame, app),
         AppState::Search => draw_search(frame, app),
         AppState::SearchMenu => draw_search_menu(frame, app),
         AppState::Credit => draw_credit(frame, app),
-        AppState::Article => draw_article(frame, app),
+        AppState::Article => {
+            draw_article(frame, app);
+        }
+        AppState::ArticleSearch => draw_article_search(frame, app),
+        AppState::LinkHints => draw_link_hints(frame, app),
         AppState::ArticleMenu => draw_article_menu(frame, app),
+        AppState::Contents => draw_contents(frame, app),
         AppState::ThemeMenu => draw_theme_selection(frame, app),
+        AppState::CommandPalette => draw_command_palette(frame, app),
+        AppState::Bookmarks => draw_bookmarks(frame, app),
+        AppState::History => draw_history(frame, app),
+        AppState::ThemeEditor => draw_theme_editor(frame, app),
         // _ => draw_search(frame, app),
     }
+}
 
-    // Render debug text
-    if app.debug_text.len() > 0 {
-        let debug_paragraph = Paragraph::new(app.debug_text.clone());
-        frame.render_widget(debug_paragraph, frame.area());
+fn draw_command_palette(frame: &mut Frame, app: &App) {
+    draw_state(frame, app, app.command_palette.return_state);
+    frame.render_widget(AlphaBox::new(Color::DarkGray, 50), frame.area());
+
+    let area = centered_rect(60, 70, frame.area());
+    let palette_areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    frame.render_widget(Eraser {}, area);
+    frame.render_widget(Block::default().style(app.theme.block_border_focus()), area);
+    let input_widget = TextBox::new(
+        app.command_palette.get_input(),
+        app.command_palette.get_cursor_pos(),
+    )
+    .cursor_style(app.theme.cursor_style())
+    .text_style(app.theme.block_border_focus());
+    frame.render_widget(input_widget, palette_areas[0]);
+
+    let visible_rows = palette_areas[1].height.saturating_sub(2) as usize;
+    let selected_index = app.command_palette.get_index();
+    let first_row = (selected_index + 1).saturating_sub(visible_rows);
+    let command_items = create_option_spans(
+        app.command_palette.get_options(),
+        selected_index,
+        &app.theme,
+    );
+    let commands_title = format!(
+        "Commands ({}/{})",
+        app.command_palette.total_options(),
+        app.command_palette.items.len()
+    );
+    frame.render_widget(
+        Paragraph::new(command_items)
+            .style(app.theme.block_border_focus())
+            .block(Block::default().borders(Borders::ALL).title(commands_title))
+            .scroll((first_row as u16, 0)),
+        palette_areas[1],
+    );
+}
+
+fn draw_bookmarks(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(app.config.margin)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(frame.area());
+
+    let bookmarks = &app.bookmarks;
+    let input_widget = TextBox::new(bookmarks.get_input(), bookmarks.get_cursor_pos())
+        .cursor_style(app.theme.cursor_style())
+        .text_style(app.theme.block_border_focus());
+    frame.render_widget(input_widget, chunks[0]);
+
+    let filtered_indices = bookmarks.filtered_indices();
+    let bookmark_lines: Vec<Line> = filtered_indices
+        .iter()
+        .enumerate()
+        .flat_map(|(list_index, &bookmark_index)| -> Vec<Line> {
+            let bookmark = &bookmarks.store.bookmarks[bookmark_index];
+            let title_style = if list_index == bookmarks.selected_index {
+                app.theme.highlighted_title_style()
+            } else {
+                app.theme.unhighlighted_title_style()
+            };
+            let mut title_spans = vec![Span::styled(bookmark.title.clone(), title_style)];
+            if let Some(section) = &bookmark.section {
+                title_spans.push(Span::styled(
+                    format!(" § {section}"),
+                    app.theme.unhighlighted_snippet_style(),
+                ));
+            }
+            let mut details = format!(
+                "{} · {}",
+                bookmark.wiki.name,
+                format_timestamp(bookmark.created_at, "%Y-%m-%d %H:%M")
+            );
+            for tag in &bookmark.tags {
+                details.push_str(&format!(" #{tag}"));
+            }
+            vec![
+                Line::from(title_spans),
+                Line::from(Span::styled(details, app.theme.loading())),
+            ]
+        })
+        .collect();
+
+    let bookmarks_title = match bookmarks.editing_tags {
+        Some(_) => String::from("Tags (comma separated, enter to save)"),
+        None => format!(
+            "Bookmarks ({}/{})",
+            filtered_indices.len(),
+            bookmarks.store.bookmarks.len()
+        ),
+    };
+    // Every bookmark takes two lines
+    let visible_bookmarks = (chunks[1].height.saturating_sub(2) / 2) as usize;
+    let first_bookmark = (bookmarks.selected_index + 1).saturating_sub(visible_bookmarks);
+    frame.render_widget(
+        Paragraph::new(bookmark_lines)
+            .style(app.theme.block_border_focus())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(bookmarks_title),
+            )
+            .scroll(((first_bookmark * 2) as u16, 0)),
+        chunks[1],
+    );
+}
+
+fn draw_history(frame: &mut Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(app.config.margin)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(frame.area());
+
+    let history = &app.history;
+    let input_widget = TextBox::new(history.get_input(), history.get_cursor_pos())
+        .cursor_style(app.theme.cursor_style())
+        .text_style(app.theme.block_border_focus());
+    frame.render_widget(input_widget, chunks[0]);
+
+    let filtered_indices = history.filtered_indices();
+    let mut history_lines: Vec<Line> = vec![];
+    let mut selected_line = 0;
+    let mut current_day = String::new();
+    for (list_index, &entry_index) in filtered_indices.iter().enumerate() {
+        let entry = &history.log.entries[entry_index];
+        let day = entry.day();
+        if day != current_day {
+            if !history_lines.is_empty() {
+                history_lines.push(Line::from(""));
+            }
+            history_lines.push(Line::from(Span::styled(
+                day.clone(),
+                app.theme.loading().add_modifier(Modifier::BOLD),
+            )));
+            current_day = day;
+        }
+
+        let title_style = if list_index == history.selected_index {
+            selected_line = history_lines.len();
+            app.theme.highlighted_title_style()
+        } else {
+            app.theme.unhighlighted_title_style()
+        };
+        history_lines.push(Line::from(vec![
+            Span::styled(
+                format!("  {}  ", format_timestamp(entry.visited_at, "%H:%M")),
+                app.theme.loading(),
+            ),
+            Span::styled(entry.title.clone(), title_style),
+            Span::styled(
+                format!(" · {}", entry.wiki.name),
+                app.theme.unhighlighted_snippet_style(),
+            ),
+        ]));
     }
+
+    let history_title = format!(
+        "History ({}/{})",
+        filtered_indices.len(),
+        history.log.entries.len()
+    );
+    let visible_rows = chunks[1].height.saturating_sub(2) as usize;
+    let first_row = (selected_line + 1).saturating_sub(visible_rows);
+    frame.render_widget(
+        Paragraph::new(history_lines)
+            .style(app.theme.block_border_focus())
+            .block(Block::default().borders(Borders::ALL).title(history_title))
+            .scroll((first_row as u16, 0)),
+        chunks[1],
+    );
 }
 
 fn draw_article_menu(frame: &mut Frame, app: &App) {
@@ -49,6 +246,85 @@ fn draw_article_menu(frame: &mut Frame, app: &App) {
     draw_menu(frame, app, &app.article_menu);
 }
 
+fn draw_article_search(frame: &mut Frame, app: &App) {
+    draw_article(frame, app);
+
+    let article_area = frame.area();
+    let search_box_height = 3.min(article_area.height);
+    let area = Rect {
+        y: article_area.bottom() - search_box_height,
+        height: search_box_height,
+        ..article_area
+    };
+    let input_widget = TextBox::new(
+        app.article_search.get_input(),
+        app.article_search.get_cursor_pos(),
+    )
+    .cursor_style(app.theme.cursor_style())
+    .text_style(app.theme.block_border_focus());
+
+    frame.render_widget(Eraser {}, area);
+    frame.render_widget(Block::default().style(app.theme.block_border_focus()), area);
+    frame.render_widget(input_widget, area);
+}
+
+fn draw_link_hints(frame: &mut Frame, app: &App) {
+    let (text_area, vertical_scroll) = draw_article(frame, app);
+    let layout = match app.article.layout() {
+        Some(layout) => layout,
+        None => return,
+    };
+
+    for hint in app.link_hints.matching_hints() {
+        let (line, column) = match layout.position_of_span(hint.span_index) {
+            Some(position) => position,
+            None => continue,
+        };
+        let row = match line.checked_sub(vertical_scroll) {
+            Some(row) if row < text_area.height as usize => row,
+            _ => continue,
+        };
+        let x = text_area.x + (column as u16).min(text_area.width.saturating_sub(1));
+        let y = text_area.y + row as u16;
+        // Only the characters still to be typed are shown
+        let remaining_label = &hint.label[app.link_hints.typed.len()..];
+        frame.buffer_mut().set_stringn(
+            x,
+            y,
+            remaining_label,
+            (text_area.right() - x) as usize,
+            app.theme.link_hint_style(),
+        );
+    }
+}
+
+fn draw_contents(frame: &mut Frame, app: &App) {
+    draw_article(frame, app);
+    frame.render_widget(AlphaBox::new(Color::DarkGray, 50), frame.area());
+
+    let area = centered_rect(60, 70, frame.area());
+    let visible_rows = area.height.saturating_sub(2) as usize;
+    let selected_index = app.contents_menu.get_index();
+    // Keep the selected heading in view, roughly in the middle of the list
+    let first_row = selected_index.saturating_sub(visible_rows / 2).min(
+        app.contents_menu
+            .total_options()
+            .saturating_sub(visible_rows),
+    );
+
+    let contents_items =
+        create_option_spans(app.contents_menu.get_options(), selected_index, &app.theme);
+
+    frame.render_widget(Eraser {}, area);
+    frame.render_widget(
+        Paragraph::new(contents_items)
+            .style(app.theme.block_border_focus())
+            .block(Block::default().borders(Borders::ALL).title("Contents"))
+            .scroll((first_row as u16, 0)),
+        area,
+    );
+}
+
 fn draw_search_menu(frame: &mut Frame, app: &App) {
     draw_search(frame, app);
     frame.render_widget(AlphaBox::new(Color::DarkGray, 50), frame.area());
@@ -119,6 +395,11 @@ fn centered_rect_by_lengths(length_x: u16, length_y: u16, r: Rect) -> Rect {
 }
 
 pub fn draw_search(frame: &mut Frame, app: &App) {
+    let results_title = match app.config.offline {
+        true => "Results (offline)",
+        false => "Results",
+    };
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(app.config.margin.into())
@@ -176,9 +457,16 @@ pub fn draw_search(frame: &mut Frame, app: &App) {
                         search_result.title.clone(),
                         title_style,
                     );
+                    let title_line = match search_result.is_local {
+                        true => Line::from(vec![
+                            Span::styled("[local] ", app.theme.loading()),
+                            title_span,
+                        ]),
+                        false => Line::from(vec![title_span]),
+                    };
                     if index == selected_index {
                         vec![
-                            Line::from(vec![title_span]),
+                            title_line,
                             Line::from(SearchResult::highlighted_snippets(
                                 &search_result,
                                 &app.theme,
@@ -186,7 +474,7 @@ pub fn draw_search(frame: &mut Frame, app: &App) {
                             Line::from(vec![Span::raw("")]),
                         ]
                     } else {
-                        vec![Line::from(vec![title_span])]
+                        vec![title_line]
                     }
                 })
                 .collect(); // Collect spans into a Vec<Line>
@@ -200,7 +488,7 @@ pub fn draw_search(frame: &mut Frame, app: &App) {
             frame.render_widget(
                 Paragraph::new(all_spans)
                     .style(result_block_style)
-                    .block(Block::default().borders(Borders::ALL).title("Results"))
+                    .block(Block::default().borders(Borders::ALL).title(results_title))
                     .wrap(Wrap { trim: true }),
                 result_chunks[0],
             );
@@ -210,8 +498,8 @@ pub fn draw_search(frame: &mut Frame, app: &App) {
                 app.search.selected_index,
                 results.len(),
             )
-            .bar_style(Style::default().fg(app.theme.secondary))
-            .handle_style(Style::default().fg(app.theme.tertiary));
+            .bar_style(app.theme.scrollbar_track_style())
+            .handle_style(app.theme.scrollbar_handle_style());
             frame.render_widget(scroll_bar, result_chunks[1]);
         }
         Err(e) => {
@@ -222,7 +510,7 @@ pub fn draw_search(frame: &mut Frame, app: &App) {
             frame.render_widget(
                 Paragraph::new(Span::styled(waiting_message, app.theme.loading()))
                     .style(result_block_style)
-                    .block(Block::default().borders(Borders::ALL).title("Results")),
+                    .block(Block::default().borders(Borders::ALL).title(results_title)),
                 chunks[1],
             );
         }
@@ -302,6 +590,122 @@ fn draw_theme_selection(frame: &mut Frame, app: &App) {
     );
 }
 
+fn draw_theme_editor(frame: &mut Frame, app: &App) {
+    let editor = &app.theme_editor;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(app.config.margin)
+        .constraints(
+            [
+                Constraint::Length(THEME_EDITOR_FIELDS.len() as u16 + 2),
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(frame.area());
+
+    let field_lines: Vec<Line> = THEME_EDITOR_FIELDS
+        .iter()
+        .enumerate()
+        .map(|(field_index, label)| {
+            let style = match field_index == editor.selected_index {
+                true => app.theme.highlighted_title_style(),
+                false => app.theme.unhighlighted_title_style(),
+            };
+            let mut field_spans = vec![
+                Span::styled(format!(" {label:<14}"), style),
+                Span::styled(format!("{:<10} ", editor.field_value(field_index)), style),
+            ];
+            if field_index >= FIRST_STYLE_SLOT_FIELD {
+                let slot_style = editor.theme.style_slots()[field_index - FIRST_STYLE_SLOT_FIELD];
+                field_spans.push(Span::styled(
+                    " Sample ",
+                    Style::default()
+                        .bg(editor.theme.background)
+                        .patch(slot_style),
+                ));
+            } else if field_index > 0 {
+                let color = editor.theme.base_colors()[field_index - 1];
+                field_spans.push(Span::styled("      ", Style::default().bg(color)));
+            }
+            Line::from(field_spans)
+        })
+        .collect();
+    let editor_title = match &editor.message {
+        Some(message) => message.clone(),
+        None => String::from("Theme editor (enter to save, esc to cancel)"),
+    };
+    frame.render_widget(
+        Paragraph::new(field_lines)
+            .style(app.theme.block_border_focus())
+            .block(Block::default().borders(Borders::ALL).title(editor_title)),
+        chunks[0],
+    );
+
+    let input_widget = TextBox::new(editor.get_input(), editor.get_cursor_pos())
+        .cursor_style(app.theme.cursor_style())
+        .text_style(app.theme.block_border_focus());
+    frame.render_widget(input_widget, chunks[1]);
+
+    let sample_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .split(chunks[2]);
+    frame.render_widget(
+        Paragraph::new(sample_article_lines(&app.theme))
+            .style(app.theme.block_border_focus())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Aurora § Causes - Sample article"),
+            )
+            .wrap(Wrap { trim: true }),
+        sample_chunks[0],
+    );
+    let scroll_bar = ScrollBar::new(sample_chunks[1].height as usize, 0, 3)
+        .bar_style(app.theme.scrollbar_track_style())
+        .handle_style(app.theme.scrollbar_handle_style());
+    frame.render_widget(scroll_bar, sample_chunks[1]);
+}
+
+/// A made-up article showing off every part of a theme, for the theme editor
+fn sample_article_lines(theme: &Theme) -> Vec<Line<'static>> {
+    vec![
+        Line::from(Span::styled("Aurora", theme.heading_style(1))),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("An "),
+            Span::styled("aurora", theme.link_style(false, false)),
+            Span::raw(" is a natural light display in the sky, seen mostly in "),
+            Span::styled("high-latitude regions", theme.link_style(true, false)),
+            Span::raw(" such as "),
+            Span::styled("Scandinavia", theme.link_style(false, true)),
+            Span::raw("."),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled("Causes", theme.heading_style(2))),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("Auroras are "),
+            Span::styled("caused", theme.search_match_style()),
+            Span::raw(" by disturbances in the "),
+            Span::styled("magnetosphere", theme.link_style(false, false)),
+            Span::raw(" brought on by the solar wind."),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled("Solar wind", theme.heading_style(3))),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("The solar wind is a stream of "),
+            Span::styled("charged particles", theme.current_search_match_style()),
+            Span::raw(" given off by the upper atmosphere of the "),
+            Span::styled("Sun", theme.link_style(false, true)),
+            Span::raw("."),
+        ]),
+    ]
+}
+
 fn draw_title(frame: &mut Frame, app: &App) {
     let full_area = centered_rect_by_lengths(40, 11, frame.area());
 
@@ -331,61 +735,171 @@ fn draw_title(frame: &mut Frame, app: &App) {
     frame.render_widget(input_widget, title_areas[1]);
 }
 
-fn draw_article(frame: &mut Frame, app: &App) {
-    let article_content: Vec<Line> = match app.article.has_loaded_article.try_lock() {
+/// Draws the article and returns the area its text fills, along with the line at the top of it
+fn draw_article(frame: &mut Frame, app: &App) -> (Rect, usize) {
+    let mut area = frame.area();
+    if app.total_tabs() > 1 {
+        let tab_areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+            .split(area);
+        draw_tab_bar(frame, app, tab_areas[0]);
+        area = tab_areas[1];
+    }
+
+    match &app.split {
+        None => draw_article_pane(frame, app, &app.article, area, true),
+        Some(split) => {
+            let pane_areas = Layout::default()
+                .direction(split.direction)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(area);
+            let (first_article, second_article) = match split.focus_first {
+                true => (&app.article, &split.other_article),
+                false => (&split.other_article, &app.article),
+            };
+            let first_area =
+                draw_article_pane(frame, app, first_article, pane_areas[0], split.focus_first);
+            let second_area = draw_article_pane(
+                frame,
+                app,
+                second_article,
+                pane_areas[1],
+                !split.focus_first,
+            );
+            // Link hints and the like go on the pane with focus
+            match split.focus_first {
+                true => first_area,
+                false => second_area,
+            }
+        }
+    }
+}
+
+/// Draws one article into `area`, returning the area its text was drawn in
+/// and the line it was scrolled to, which stops at the end of the article
+fn draw_article_pane(
+    frame: &mut Frame,
+    app: &App,
+    article: &ArticleState,
+    area: Rect,
+    is_focused: bool,
+) -> (Rect, usize) {
+    let infobox = match article.infobox.try_lock() {
+        Ok(infobox) => infobox.clone(),
+        Err(_) => vec![],
+    };
+    let show_infobox = article.show_infobox && !infobox.is_empty();
+    let infobox_width = match show_infobox {
+        true => (area.width / 3).min(INFOBOX_PANEL_MAX_WIDTH),
+        false => 0,
+    };
+    let article_areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(infobox_width)].as_ref())
+        .split(area);
+    let article_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .split(article_areas[0]);
+
+    let article_block_area = Block::default()
+        .borders(Borders::ALL)
+        .inner(article_chunks[0]);
+    article.viewport_size.set((
+        article_block_area.width as usize,
+        article_block_area.height as usize,
+    ));
+
+    let mut article_title = format!("{} - {}", article.article_name, article.wiki.name);
+    let mut scroll_range: Option<(usize, usize)> = None;
+    let article_content: Vec<Line> = match article.has_loaded_article.try_lock() {
         Ok(loading_result) => match *loading_result {
             true => {
-                let vecs_of_formatted_spans = app
-                    .article
-                    .markdown_spans
-                    .lock()
-                    .unwrap()
-                    .deref()
-                    .split(|formatted_span| formatted_span.is_break)
-                    .map(|slice| -> Vec<FormattedSpan> { slice.to_vec() })
-                    .collect::<Vec<Vec<FormattedSpan>>>();
-
-                let link_span_indices = app.article.link_span_indices.lock().unwrap().clone();
+                let spans = article.markdown_spans.lock().unwrap();
+                let layout = article.layout_of(&spans);
+                let spans_by_index: HashMap<usize, &FormattedSpan> = spans
+                    .iter()
+                    .map(|formatted_span| (formatted_span.index, formatted_span))
+                    .collect();
+
+                let link_span_indices = article.link_span_indices.lock().unwrap().clone();
 
                 let selected_index = link_span_indices
-                    .get(app.article.selected_link_index)
+                    .get(article.selected_link_index)
                     .unwrap_or(&0);
+                let selected_link_spans = link_span_group(&spans, *selected_index);
+
+                let visited_titles = app.history.log.visited_titles(&article.wiki);
+
+                let current_match = app.article_search.current();
+                let mut matches_by_span: HashMap<usize, Vec<TextMatch>> = HashMap::new();
+                let pane_matches = match is_focused {
+                    true => app.article_search.matches.as_slice(),
+                    false => &[],
+                };
+                for text_match in pane_matches.iter() {
+                    matches_by_span
+                        .entry(text_match.span_index)
+                        .or_default()
+                        .push(*text_match);
+                }
+
+                // The viewport may have grown since the article was last scrolled
+                let max_scroll = layout.max_scroll(article_block_area.height as usize);
+                let vertical_scroll = article.vertical_scroll.min(max_scroll);
+                scroll_range = Some((vertical_scroll, max_scroll));
+
+                if let Some(heading) = layout.heading_at_line(&spans, vertical_scroll) {
+                    article_title = format!(
+                        "{} § {} - {}",
+                        article.article_name, heading.text, article.wiki.name
+                    );
+                }
 
-                vecs_of_formatted_spans
+                layout
+                    .lines
                     .iter()
-                    .enumerate()
-                    .map(|(_, formatted_spans)| -> Line {
+                    .skip(vertical_scroll)
+                    .take(article_block_area.height as usize)
+                    .map(|fragments| -> Line {
                         Line::from(
-                            formatted_spans
+                            fragments
                                 .iter()
-                                .enumerate()
-                                .map(|(_, formatted_span)| -> Span {
-                                    if formatted_span.is_heading {
-                                        Span::styled(
-                                            formatted_span.text.clone(),
-                                            if formatted_span.heading_level > 2 {
-                                                Style::default().add_modifier(Modifier::BOLD)
-                                            } else {
-                                                Style::default()
-                                                    .add_modifier(Modifier::BOLD)
-                                                    .add_modifier(Modifier::ITALIC)
-                                            },
-                                        )
-                                    } else if let Some(_link) = &formatted_span.link {
-                                        Span::styled(
-                                            formatted_span.text.clone(),
-                                            if selected_index.eq(&formatted_span.index) {
-                                                app.theme
-                                                    .highlighted_snippet_style()
-                                                    .add_modifier(Modifier::UNDERLINED)
-                                            } else {
-                                                app.theme
-                                                    .unhighlighted_snippet_style()
-                                                    .add_modifier(Modifier::UNDERLINED)
-                                            },
-                                        )
-                                    } else {
-                                        Span::raw(formatted_span.text.clone())
+                                .flat_map(|fragment| -> Vec<Span> {
+                                    let formatted_span = spans_by_index[&fragment.span_index];
+                                    let style = match fragment.kind {
+                                        FragmentKind::TableBorder => {
+                                            Style::default().fg(app.theme.secondary)
+                                        }
+                                        FragmentKind::TableHeader => {
+                                            Style::default().add_modifier(Modifier::BOLD)
+                                        }
+                                        FragmentKind::TableCell => Style::default(),
+                                        FragmentKind::ListMarker | FragmentKind::QuoteBar => {
+                                            Style::default().fg(app.theme.secondary)
+                                        }
+                                        FragmentKind::Text => article_text_style(
+                                            formatted_span,
+                                            &selected_link_spans,
+                                            visited_titles,
+                                            &app.theme,
+                                        ),
+                                    };
+                                    match matches_by_span.get(&fragment.span_index) {
+                                        Some(span_matches)
+                                            if fragment.kind == FragmentKind::Text =>
+                                        {
+                                            split_at_matches(
+                                                &fragment.text,
+                                                fragment.span_offset,
+                                                span_matches,
+                                                current_match,
+                                                style,
+                                                &app.theme,
+                                            )
+                                        }
+                                        _ => vec![Span::styled(fragment.text.clone(), style)],
                                     }
                                 })
                                 .collect::<Vec<Span>>(),
@@ -398,16 +912,216 @@ fn draw_article(frame: &mut Frame, app: &App) {
         },
         Err(_) => vec![Line::from(vec![Span::raw("Error loading page...")])],
     };
+    let mut article_block = Block::default().borders(Borders::ALL).title(article_title);
+    if is_focused && app.article_search.is_active() {
+        let match_counter = match app.article_search.matches.len() {
+            0 => format!(" No matches for \"{}\" ", app.article_search.input),
+            total_matches => format!(
+                " {}/{} \"{}\" ",
+                app.article_search.current_match + 1,
+                total_matches,
+                app.article_search.input
+            ),
+        };
+        article_block = article_block.title(Line::from(match_counter).right_aligned());
+    }
+    let article_style = match is_focused {
+        true => app.theme.block_border_focus(),
+        false => app.theme.block_border_unfocus(),
+    };
     frame.render_widget(
         Paragraph::new(article_content)
-            .style(app.theme.block_border_focus())
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(app.article.article_name.clone()),
-            )
+            .style(article_style)
+            .block(article_block),
+        article_chunks[0],
+    );
+
+    if let Some((vertical_scroll, max_scroll)) = scroll_range {
+        let scroll_bar = ScrollBar::new(
+            article_chunks[1].height as usize,
+            vertical_scroll,
+            max_scroll + 1,
+        )
+        .bar_style(app.theme.scrollbar_track_style())
+        .handle_style(app.theme.scrollbar_handle_style());
+        frame.render_widget(scroll_bar, article_chunks[1]);
+    }
+
+    if show_infobox {
+        draw_infobox(frame, app, article, &infobox, article_areas[1]);
+    }
+
+    let vertical_scroll = match scroll_range {
+        Some((vertical_scroll, _)) => vertical_scroll,
+        None => article.vertical_scroll,
+    };
+    (article_block_area, vertical_scroll)
+}
+
+const INFOBOX_PANEL_MAX_WIDTH: u16 = 40;
+const TAB_TITLE_MAX_WIDTH: usize = 24;
+
+fn draw_tab_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let tab_titles: Vec<String> = app
+        .tab_titles()
+        .iter()
+        .enumerate()
+        .map(|(tab_index, title)| {
+            let title = match title.chars().count() > TAB_TITLE_MAX_WIDTH {
+                true => format!(
+                    "{}…",
+                    title
+                        .chars()
+                        .take(TAB_TITLE_MAX_WIDTH - 1)
+                        .collect::<String>()
+                ),
+                false => title.clone(),
+            };
+            format!("{} {}", tab_index + 1, title)
+        })
+        .collect();
+    frame.render_widget(
+        Tabs::new(tab_titles)
+            .select(app.active_tab)
+            .style(app.theme.unselected_option())
+            .highlight_style(app.theme.selected_option())
+            .divider("│"),
+        area,
+    );
+}
+
+fn article_text_style(
+    formatted_span: &FormattedSpan,
+    selected_link_spans: &[usize],
+    visited_titles: Option<&HashSet<String>>,
+    theme: &Theme,
+) -> Style {
+    let style = if formatted_span.is_heading {
+        theme.heading_style(formatted_span.heading_level)
+    } else if let Some(link) = &formatted_span.link {
+        theme.link_style(
+            selected_link_spans.contains(&formatted_span.index),
+            visited_titles.is_some_and(|titles| titles.contains(&link_target_title(link))),
+        )
+    } else if formatted_span.style.code {
+        theme.inline_code_style()
+    } else {
+        Style::default()
+    };
+    style.add_modifier(inline_modifier(formatted_span.style))
+}
+
+fn inline_modifier(inline_style: InlineStyle) -> Modifier {
+    let mut modifier = Modifier::empty();
+    if inline_style.bold {
+        modifier |= Modifier::BOLD;
+    }
+    if inline_style.italic {
+        modifier |= Modifier::ITALIC;
+    }
+    if inline_style.strikethrough {
+        modifier |= Modifier::CROSSED_OUT;
+    }
+    modifier
+}
+
+/// Breaks a fragment of article text into spans so that any search matches within it stand out.
+/// `span_offset` is where the fragment starts within its span's text.
+fn split_at_matches<'a>(
+    text: &str,
+    span_offset: usize,
+    span_matches: &[TextMatch],
+    current_match: Option<TextMatch>,
+    style: Style,
+    theme: &Theme,
+) -> Vec<Span<'a>> {
+    let fragment_end = span_offset + text.len();
+    let mut pieces = Vec::new();
+    let mut position = span_offset;
+    for text_match in span_matches
+        .iter()
+        .filter(|text_match| text_match.end > span_offset && text_match.start < fragment_end)
+    {
+        let match_start = text_match.start.max(position);
+        let match_end = text_match.end.min(fragment_end);
+        if match_start > position {
+            pieces.push(Span::styled(
+                text[position - span_offset..match_start - span_offset].to_string(),
+                style,
+            ));
+        }
+        let match_style = match current_match == Some(*text_match) {
+            true => theme.current_search_match_style(),
+            false => theme.search_match_style(),
+        };
+        pieces.push(Span::styled(
+            text[match_start - span_offset..match_end - span_offset].to_string(),
+            match_style,
+        ));
+        position = match_end;
+    }
+    if position < fragment_end {
+        pieces.push(Span::styled(
+            text[position - span_offset..].to_string(),
+            style,
+        ));
+    }
+    pieces
+}
+
+fn draw_infobox(
+    frame: &mut Frame,
+    app: &App,
+    article: &ArticleState,
+    infobox: &[InfoboxEntry],
+    area: Rect,
+) {
+    let infobox_lines: Vec<Line> = infobox
+        .iter()
+        .flat_map(|entry| -> Vec<Line> {
+            if entry.value.is_empty() {
+                vec![Line::from(Span::styled(
+                    entry.label.clone(),
+                    Style::default()
+                        .fg(app.theme.tertiary)
+                        .add_modifier(Modifier::BOLD),
+                ))]
+            } else {
+                vec![
+                    Line::from(Span::styled(
+                        entry.label.clone(),
+                        Style::default()
+                            .fg(app.theme.secondary)
+                            .add_modifier(Modifier::BOLD),
+                    )),
+                    Line::from(Span::styled(
+                        entry.value.clone(),
+                        app.theme.unhighlighted_snippet_style(),
+                    )),
+                ]
+            }
+        })
+        .collect();
+
+    let infobox_block = Block::default().borders(Borders::ALL).title("Infobox");
+    let text_area = infobox_block.inner(area);
+    let total_rows = infobox_lines
+        .iter()
+        .map(|line| {
+            line.width()
+                .div_ceil(text_area.width.max(1) as usize)
+                .max(1)
+        })
+        .sum();
+    article
+        .infobox_size
+        .set((total_rows, text_area.height as usize));
+    frame.render_widget(
+        Paragraph::new(infobox_lines)
+            .style(app.theme.block_border_unfocus())
+            .block(infobox_block)
             .wrap(Wrap { trim: true })
-            .scroll((app.article.vertical_scroll as u16, 0)),
-        frame.area(),
+            .scroll((article.infobox_scroll as u16, 0)),
+        area,
     );
 }
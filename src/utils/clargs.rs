@@ -7,8 +7,12 @@ use clap::Parser;
 use dirs::home_dir;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Parser, Serialize, Deserialize)]
+use crate::caching::{DEFAULT_MAX_SIZE_MB, DEFAULT_TTL_HOURS};
+use crate::styles::ColorSupport;
+
+#[derive(Debug, PartialEq, Clone, Parser, Serialize, Deserialize)]
 #[command(version, about, long_about = None)]
+#[serde(default)]
 pub struct Args {
     /// Search query for Wikipedia page (eg. hotdogs)
     #[arg(short, long)]
@@ -25,6 +29,49 @@ pub struct Args {
     /// Margin size of application (defaults to no margin)
     #[arg(short, long, default_value_t = 0)]
     pub margin: u16,
+    /// Language edition of the wiki to browse (eg. de, fr, ja - defaults to en)
+    #[arg(short, long)]
+    pub lang: Option<String>,
+    /// Wiki to browse, either a Wikimedia project (eg. wiktionary) or the URL of a MediaWiki site
+    #[arg(short, long)]
+    pub wiki: Option<String>,
+    /// Hours before a cached page is checked with the wiki again (defaults to 24)
+    #[arg(long, default_value_t = DEFAULT_TTL_HOURS)]
+    pub cache_ttl: u64,
+    /// Maximum size of the on-disk cache in megabytes (defaults to 100)
+    #[arg(long, default_value_t = DEFAULT_MAX_SIZE_MB)]
+    pub cache_size: u64,
+    /// Move the link selection to the first link on screen whenever an article is scrolled
+    #[arg(long)]
+    pub snap_links: bool,
+    /// Name of the theme to use (eg. Autumn)
+    #[arg(long)]
+    pub theme: Option<String>,
+    /// Delete every cached page and search result before starting
+    #[arg(long)]
+    #[serde(skip)]
+    pub clear_cache: bool,
+    /// Browse without touching the network, using only cached pages and searches
+    #[arg(long)]
+    #[serde(skip)]
+    pub offline: bool,
+    /// Start on the bookmarks screen
+    #[arg(long)]
+    #[serde(skip)]
+    pub bookmarks: bool,
+    /// Print every article visited, oldest first, and exit
+    #[arg(long)]
+    #[serde(skip)]
+    pub history: bool,
+    /// Pick up where wik was left when it last quit: tabs, history, theme, search and screen
+    #[arg(long)]
+    #[serde(skip)]
+    pub restore: bool,
+    /// Colours the terminal can show: truecolor, 256, 16 or never (worked out from
+    /// COLORTERM, TERM and NO_COLOR by default)
+    #[arg(long)]
+    #[serde(skip)]
+    pub color: Option<ColorSupport>,
 }
 
 impl Default for Args {
@@ -35,19 +82,45 @@ impl Default for Args {
             rows: None,
             cols: None,
             margin: 0,
+            lang: None,
+            wiki: None,
+            cache_ttl: DEFAULT_TTL_HOURS,
+            cache_size: DEFAULT_MAX_SIZE_MB,
+            snap_links: false,
+            theme: None,
+            clear_cache: false,
+            offline: false,
+            bookmarks: false,
+            history: false,
+            restore: false,
+            color: None,
         }
     }
 }
 
 impl Args {
     pub fn is_default_configs(&self) -> bool {
-        self.rows.is_none() && self.cols.is_none() && (self.margin == 0)
+        self.rows.is_none()
+            && self.cols.is_none()
+            && (self.margin == 0)
+            && self.lang.is_none()
+            && self.wiki.is_none()
+            && (self.cache_ttl == DEFAULT_TTL_HOURS)
+            && (self.cache_size == DEFAULT_MAX_SIZE_MB)
+            && !self.snap_links
+            && self.theme.is_none()
     }
 
     pub fn load_from(&mut self, other: Args) {
         self.rows = other.rows;
         self.cols = other.cols;
         self.margin = other.margin;
+        self.lang = other.lang;
+        self.wiki = other.wiki;
+        self.cache_ttl = other.cache_ttl;
+        self.cache_size = other.cache_size;
+        self.snap_links = other.snap_links;
+        self.theme = other.theme;
     }
 }
 
@@ -84,3 +157,10 @@ pub fn save_arg_to_file(args: &Args) -> io::Result<()> {
     }
     Ok(())
 }
+
+/// Saves `theme_name` as the theme to use, leaving the rest of the config file as it was
+pub fn save_theme_to_config(theme_name: &str) -> io::Result<()> {
+    let mut saved_args = load_arg_from_config().unwrap_or_default();
+    saved_args.theme = Some(theme_name.to_string());
+    save_arg_to_file(&saved_args)
+}
@@ -1,6 +1,8 @@
 use std::ops::{Add, Rem, Sub};
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use chrono::{Local, TimeZone};
 use ratatui::style::Color;
 
 pub const WIK_TITLE: &str = r"
@@ -20,6 +22,21 @@ pub fn shared_copy<T>(value_to_copy: &Shared<T>) -> Shared<T> {
     Arc::clone(value_to_copy)
 }
 
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats a Unix timestamp (seconds) in local time, eg. with `"%Y-%m-%d %H:%M"`
+pub fn format_timestamp(timestamp: u64, format: &str) -> String {
+    match Local.timestamp_opt(timestamp as i64, 0).single() {
+        Some(date_time) => date_time.format(format).to_string(),
+        None => String::from("?"),
+    }
+}
+
 pub fn remainder<T: Add + Sub + Rem + Copy>(
     dividend: T,
     divisor: T,
@@ -94,12 +111,107 @@ pub fn try_color_as_rgb(color: Color) -> Color {
         Color::LightCyan => return Color::Rgb(41, 184, 219),
         Color::White => return Color::Rgb(229, 229, 229),
         Color::Rgb(r, g, b) => return Color::Rgb(r, g, b),
+        Color::Indexed(index) => {
+            let (r, g, b) = indexed_to_rgb(index);
+            return Color::Rgb(r, g, b);
+        }
         _ => {}
     }
 
     return color;
 }
 
+/// The named colours in the order of the first 16 entries of the 256-colour palette
+const ANSI_16_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+/// Levels each of red, green and blue can take in the 6x6x6 colour cube of the 256-colour palette
+const ANSI_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The RGB value terminals usually give an entry of the 256-colour palette
+pub fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => match try_color_as_rgb(ANSI_16_COLORS[index as usize]) {
+            Color::Rgb(r, g, b) => (r, g, b),
+            _ => (0, 0, 0),
+        },
+        16..=231 => {
+            let cube_index = (index - 16) as usize;
+            (
+                ANSI_CUBE_LEVELS[cube_index / 36],
+                ANSI_CUBE_LEVELS[(cube_index / 6) % 6],
+                ANSI_CUBE_LEVELS[cube_index % 6],
+            )
+        }
+        _ => {
+            let gray = 8 + 10 * (index - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+fn color_distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let channel_distance = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    channel_distance(a.0, b.0) + channel_distance(a.1, b.1) + channel_distance(a.2, b.2)
+}
+
+/// The entry of the 256-colour palette closest to an RGB colour. Other colours are left alone.
+pub fn nearest_ansi256_color(color: Color) -> Color {
+    let (r, g, b) = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => return color,
+    };
+    let nearest_level = |value: u8| -> usize {
+        ANSI_CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &level)| (level as i32 - value as i32).abs())
+            .map(|(level_index, _)| level_index)
+            .unwrap_or(0)
+    };
+    let cube_index = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = 232 + (average.saturating_sub(3) / 10).min(23) as usize;
+
+    let nearest_index = [cube_index, gray_index]
+        .into_iter()
+        .min_by_key(|&index| color_distance((r, g, b), indexed_to_rgb(index as u8)))
+        .unwrap_or(cube_index);
+    Color::Indexed(nearest_index as u8)
+}
+
+/// The named colour closest to `color`. `Reset` is left alone.
+pub fn nearest_ansi16_color(color: Color) -> Color {
+    let rgb = match try_color_as_rgb(color) {
+        _ if ANSI_16_COLORS.contains(&color) => return color,
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => return color,
+    };
+    ANSI_16_COLORS
+        .into_iter()
+        .min_by_key(|&named_color| match try_color_as_rgb(named_color) {
+            Color::Rgb(r, g, b) => color_distance(rgb, (r, g, b)),
+            _ => u32::MAX,
+        })
+        .unwrap_or(color)
+}
+
 pub fn wrapped_iter_enumerate<T>(vec: &Vec<T>, start: usize) -> impl Iterator<Item = (usize, &T)> {
     let len = vec.len();
     (0..len).map(move |i| {
@@ -111,3 +223,40 @@ pub fn wrapped_iter_enumerate<T>(vec: &Vec<T>, start: usize) -> impl Iterator<It
 pub fn cut_off_from_char(text: &str, delimiter: char) -> &str {
     text.splitn(2, delimiter).next().unwrap_or(&text).trim()
 }
+
+/// Scores how well `query` matches `candidate` when its characters appear in order,
+/// but not necessarily next to each other, ignoring case. Runs of consecutive characters
+/// and matches at the start of words score higher. `None` if it doesn't match at all.
+pub fn fuzzy_match_score(query: &str, candidate: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut query_chars = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut previous_matched = false;
+    let mut previous_char = ' ';
+    for (position, c) in candidate.chars().flat_map(char::to_lowercase).enumerate() {
+        let query_char = match query_chars.peek() {
+            Some(&query_char) => query_char,
+            None => break,
+        };
+        if c == query_char {
+            query_chars.next();
+            score += 1;
+            if previous_matched {
+                score += 5;
+            }
+            if !previous_char.is_alphanumeric() {
+                score += 3;
+            }
+            if position == 0 {
+                score += 2;
+            }
+            previous_matched = true;
+        } else {
+            previous_matched = false;
+        }
+        previous_char = c;
+    }
+    match query_chars.peek() {
+        Some(_) => None,
+        None => Some(score),
+    }
+}
@@ -0,0 +1,30 @@
+use ratatui::{buffer::Buffer, layout::Rect, widgets::Widget};
+
+use crate::styles::ColorSupport;
+
+/// Swaps every colour in an area for the closest one the terminal can show
+pub struct ColorFilter {
+    color_support: ColorSupport,
+}
+
+impl ColorFilter {
+    pub fn new(color_support: ColorSupport) -> Self {
+        Self { color_support }
+    }
+}
+
+impl Widget for ColorFilter {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if self.color_support == ColorSupport::TrueColor {
+            return;
+        }
+        for x in (area.x)..(area.width + area.x) {
+            for y in (area.y)..(area.height + area.y) {
+                if let Some(cell) = buf.cell_mut((x, y)) {
+                    cell.set_bg(self.color_support.adapt_color(cell.bg));
+                    cell.set_fg(self.color_support.adapt_color(cell.fg));
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
 pub mod alphabox;
+pub mod colorfilter;
 pub mod eraser;
 pub mod scrollbar;
 pub mod textbox;
 pub use alphabox::AlphaBox;
+pub use colorfilter::ColorFilter;
 pub use eraser::Eraser;
 pub use scrollbar::ScrollBar;
 pub use textbox::TextBox;
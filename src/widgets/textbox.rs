@@ -45,49 +45,32 @@ impl Widget for TextBox {
             inner_area
         };
 
-        let text_to_render = format!("{} ", self.text.clone().as_str());
-        let text_len = text_to_render.len();
-        if self.cursor_pos < inner_area.width as usize {
-            for x in 0..text_len {
-                if x >= inner_area.width as usize {
-                    break;
-                }
-                let char = &text_to_render[x..x + 1];
-                buf.set_string(
-                    inner_area.x + x as u16,
-                    inner_area.y,
-                    char.to_string(),
-                    if x == self.cursor_pos {
-                        self.cursor_style
-                    } else {
-                        self.text_style
-                    },
-                );
-            }
-        } else {
-            for x in 0..(inner_area.width) {
-                let char_index =
-                    (self.cursor_pos + (x + 1) as usize).saturating_sub(inner_area.width as usize);
-
-                if char_index > text_len {
-                    break;
-                }
-                buf.set_string(
-                    inner_area.x + x,
-                    inner_area.y,
-                    text_to_render
-                        .chars()
-                        .nth(char_index)
-                        .unwrap_or(' ')
-                        .to_string(),
-                    if char_index as usize == self.cursor_pos {
-                        self.cursor_style
-                    } else {
-                        self.text_style
-                        // Style::default().bg(Color::LightMagenta)
-                    },
-                );
+        // The cursor is a byte index, but the text is drawn a character at a time
+        let text_to_render: Vec<char> = format!("{} ", self.text).chars().collect();
+        let cursor_index = match self.text.get(..self.cursor_pos) {
+            Some(before_cursor) => before_cursor.chars().count(),
+            None => self.text.chars().count(),
+        };
+        // Once the cursor passes the right edge, the text scrolls to keep it in the last column
+        let first_index = (cursor_index + 1).saturating_sub(inner_area.width as usize);
+        let mut x = inner_area.x;
+        for (char_index, c) in text_to_render.iter().enumerate().skip(first_index) {
+            if x >= inner_area.right() {
+                break;
             }
+            let (next_x, _) = buf.set_stringn(
+                x,
+                inner_area.y,
+                c.to_string(),
+                (inner_area.right() - x) as usize,
+                if char_index == cursor_index {
+                    self.cursor_style
+                } else {
+                    self.text_style
+                },
+            );
+            // Characters with no width, like combining accents, still take a column here
+            x = next_x.max(x + 1);
         }
     }
 }
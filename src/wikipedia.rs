@@ -1,11 +1,16 @@
 use htmd::HtmlToMarkdown;
+use percent_encoding::percent_decode_str;
 use ratatui::text::Span;
-use reqwest::blocking::Client;
-use serde::{Deserialize, Serialize};
-use std::{error::Error, thread};
+use regex::Regex;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, thread};
 
+use crate::caching::{read_cache_file, Url};
 use crate::parsing;
-use crate::parsing::FormattedSpan;
+use crate::parsing::{FormattedSpan, InfoboxEntry, InlineStyle};
 use crate::{caching::CachingSession, styles::Theme, utils::Shared};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -13,12 +18,137 @@ pub struct SearchResult {
     pub title: String,
     pub pageid: i32,
     pub snippet: String,
+    /// Found by searching the cached pages rather than by the wiki
+    #[serde(default)]
+    pub is_local: bool,
 }
 
 const OPENING_TAG: &str = "<span class=\"searchmatch\">";
 const CLOSING_TAG: &str = "</span>";
 
 const SEARCH_RESULT_LIMIT: u16 = 25;
+// Characters of context either side of the match in a local search snippet
+const LOCAL_SNIPPET_CONTEXT: usize = 60;
+
+const DEFAULT_LANGUAGE: &str = "en";
+const DEFAULT_SCRIPT_PATH: &str = "/w";
+
+// Wikimedia projects that can be named directly with `--wiki`, eg. `--wiki wiktionary`
+const WIKIMEDIA_PROJECTS: [&str; 8] = [
+    "wikipedia",
+    "wiktionary",
+    "wikibooks",
+    "wikiquote",
+    "wikisource",
+    "wikinews",
+    "wikiversity",
+    "wikivoyage",
+];
+
+/// The MediaWiki site that searches and pages are loaded from.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Deserialize, Serialize)]
+pub struct WikiSource {
+    pub name: String,
+    pub base_url: String,
+    pub api_path: String,
+    pub rest_path: String,
+}
+
+impl Default for WikiSource {
+    fn default() -> Self {
+        WikiSource::wikimedia_project("wikipedia", DEFAULT_LANGUAGE)
+    }
+}
+
+impl WikiSource {
+    pub fn wikimedia_project(project: &str, lang: &str) -> Self {
+        let host = format!("{lang}.{project}.org");
+        Self::from_base_url(host.clone(), format!("https://{host}"), DEFAULT_SCRIPT_PATH)
+    }
+
+    /// Any MediaWiki site, eg. `http://localhost:8080` or `https://wiki.example.com/w`.
+    /// If the URL has a path, it is used as the site's script path, otherwise `/w` is assumed.
+    pub fn from_url(url: &str) -> Self {
+        let url = url.trim().trim_end_matches('/');
+        let url = match url.contains("://") {
+            true => url.to_string(),
+            false => format!("https://{url}"),
+        };
+
+        let scheme_end = url.find("://").map(|pos| pos + 3).unwrap_or(0);
+        let (base_url, script_path) = match url[scheme_end..].find('/') {
+            Some(path_start) => url.split_at(scheme_end + path_start),
+            None => (url.as_str(), DEFAULT_SCRIPT_PATH),
+        };
+        let name = base_url[scheme_end..].to_string();
+
+        Self::from_base_url(name, base_url.to_string(), script_path)
+    }
+
+    /// Picks the wiki from the `--lang` and `--wiki` arguments, defaulting to English Wikipedia.
+    pub fn from_args(lang: Option<&str>, wiki: Option<&str>) -> Self {
+        let lang = lang.unwrap_or(DEFAULT_LANGUAGE);
+        match wiki {
+            Some(project) if WIKIMEDIA_PROJECTS.contains(&project.to_lowercase().as_str()) => {
+                Self::wikimedia_project(&project.to_lowercase(), lang)
+            }
+            Some(url) => Self::from_url(url),
+            None => Self::wikimedia_project("wikipedia", lang),
+        }
+    }
+
+    fn from_base_url(name: String, base_url: String, script_path: &str) -> Self {
+        Self {
+            name,
+            base_url,
+            api_path: format!("{script_path}/api.php"),
+            rest_path: format!("{script_path}/rest.php/v1/page"),
+        }
+    }
+
+    /// The URL of `path` on this wiki, with `add_to_url` appending the encoded query or segments
+    fn endpoint_url(&self, path: &str, add_to_url: impl FnOnce(&mut reqwest::Url)) -> String {
+        let endpoint = format!("{}{path}", self.base_url);
+        match reqwest::Url::parse(&endpoint) {
+            Ok(mut url) => {
+                add_to_url(&mut url);
+                url.into()
+            }
+            // Nothing can be fetched from a wiki whose address isn't a URL, so leave it as it is
+            Err(_) => endpoint,
+        }
+    }
+
+    pub fn search_url(&self, query: &str) -> String {
+        self.endpoint_url(&self.api_path, |url| {
+            url.query_pairs_mut()
+                .append_pair("action", "query")
+                .append_pair("list", "search")
+                .append_pair("srsearch", query)
+                .append_pair("srlimit", &SEARCH_RESULT_LIMIT.to_string())
+                .append_pair("format", "json");
+        })
+    }
+
+    pub fn page_url(&self, page_title: &str) -> String {
+        self.endpoint_url(&self.rest_path, |url| {
+            if let Ok(mut segments) = url.path_segments_mut() {
+                segments.push(page_title).push("html");
+            }
+        })
+    }
+
+    /// The page title in a URL made by `page_url`, or None if it isn't a page of this wiki
+    pub fn title_from_page_url(&self, url: &str) -> Option<String> {
+        let encoded_title = url
+            .strip_prefix(&format!("{}/", self.endpoint_url(&self.rest_path, |_| ())))?
+            .strip_suffix("/html")?;
+        percent_decode_str(encoded_title)
+            .decode_utf8()
+            .ok()
+            .map(String::from)
+    }
+}
 
 impl SearchResult {
     pub fn highlighted_snippets<'a>(
@@ -68,30 +198,96 @@ struct WikiSearchResponse {
 pub struct WikiPageData {
     pub title: String,
     pub markdown_content: String,
+    #[serde(default)]
+    pub infobox: Vec<InfoboxEntry>,
+}
+
+pub struct WikiPage {
+    pub spans: Vec<FormattedSpan>,
+    pub infobox: Vec<InfoboxEntry>,
+}
+
+/// The parts of an article that a background load fills in once the page arrives
+pub struct SharedArticle {
+    pub has_loaded_flag: Shared<bool>,
+    pub markdown_spans: Shared<Vec<FormattedSpan>>,
+    /// Bumped every time `markdown_spans` is replaced
+    pub spans_generation: Shared<usize>,
+    pub link_indices: Shared<Vec<usize>>,
+    pub infobox: Shared<Vec<InfoboxEntry>>,
+    pub is_valid_page: Shared<bool>,
+}
+
+/// Loads `url` through the cache. A fresh cached copy is used as-is, a stale one is
+/// revalidated with its ETag, and if the wiki can't be reached or answers with an error any
+/// stale copy is used instead. Only successful responses are cached.
+fn fetch_through_cache<T, F>(
+    url: &Url,
+    caching_session: &mut CachingSession,
+    convert_response: F,
+) -> Option<T>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce(Response) -> Option<T>,
+{
+    if caching_session.offline || caching_session.is_fresh(url) {
+        return caching_session.get_from_cache::<T>(url);
+    }
+
+    let mut request = Client::new().get(url);
+    if let Some(etag) = caching_session.get_etag(url) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    match request.send() {
+        Ok(response) if response.status() == StatusCode::NOT_MODIFIED => {
+            caching_session.refresh(url);
+            caching_session.get_from_cache::<T>(url)
+        }
+        Ok(response) if response.status().is_success() => {
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(String::from);
+            let fresh_object = convert_response(response)?;
+            caching_session
+                .write_to_cache(url, &fresh_object, etag)
+                .unwrap_or(());
+            Some(fresh_object)
+        }
+        Ok(_) | Err(_) => caching_session.get_from_cache::<T>(url),
+    }
 }
 
 pub fn get_wikipedia_query(
     query: &str,
+    wiki: &WikiSource,
     shared_caching_session: Shared<CachingSession>,
 ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
-    let url = format!(
-        "https://en.wikipedia.org/w/api.php?action=query&list=search&srsearch={}&srlimit={SEARCH_RESULT_LIMIT}&format=json",
-        query
-    );
+    let url = wiki.search_url(query);
     let mut caching_session = shared_caching_session.lock().unwrap();
 
-    let query_response: Option<WikiSearchResponse> = match caching_session.has_url(&url) {
-        true => {
-            // get form cache
-            caching_session.get_from_cache::<WikiSearchResponse>(&url)
-        }
-        false => {
-            let client = Client::new();
-            let fresh_response = client.get(&url).send()?.json::<WikiSearchResponse>()?;
-            caching_session.write_to_cache(&url, &fresh_response)?;
-            Some(fresh_response)
+    if caching_session.offline {
+        let mut results = match caching_session.get_from_cache::<WikiSearchResponse>(&url) {
+            Some(response) => response.query.search,
+            None => vec![],
+        };
+        for local_result in search_cached_pages(query, wiki, &caching_session) {
+            if !results
+                .iter()
+                .any(|result| result.title == local_result.title)
+            {
+                results.push(local_result);
+            }
         }
-    };
+        return Ok(results);
+    }
+
+    let query_response: Option<WikiSearchResponse> =
+        fetch_through_cache(&url, &mut caching_session, |response| {
+            response.json::<WikiSearchResponse>().ok()
+        });
 
     match query_response {
         Some(response) => Ok(response.query.search),
@@ -99,41 +295,35 @@ pub fn get_wikipedia_query(
     }
 }
 
+/// Converts a page's Parsoid HTML to the Markdown that `parsing::parse_markdown` reads
+pub fn markdown_of_html(html_content: &str) -> String {
+    let converter = HtmlToMarkdown::builder()
+        .skip_tags(vec!["script", "style", "sup"])
+        .add_handler(vec!["table"], parsing::table_handler)
+        .add_handler(vec!["s", "del", "strike"], parsing::strikethrough_handler)
+        .add_handler(vec!["pre"], parsing::preformatted_handler)
+        .build();
+
+    converter.convert(html_content).unwrap_or_default()
+}
+
 pub fn get_wikipedia_page(
     page_title: &str,
+    wiki: &WikiSource,
     shared_caching_session: Shared<CachingSession>,
-) -> Result<Vec<FormattedSpan>, Box<dyn Error>> {
-    let url = format!("https://en.wikipedia.org/w/rest.php/v1/page/{page_title}/html");
+) -> Result<WikiPage, Box<dyn Error>> {
+    let url = wiki.page_url(page_title);
     let mut caching_session = shared_caching_session.lock().unwrap();
 
-    let page_data_response: Option<WikiPageData> = match caching_session.has_url(&url.clone()) {
-        true => caching_session.get_from_cache::<WikiPageData>(&url),
-        false => {
-            if let Ok(response) = reqwest::blocking::get(url.clone()) {
-                if let Ok(html_content) = response.text() {
-                    let converter = HtmlToMarkdown::builder()
-                        .skip_tags(vec!["script", "style", "table", "sup"])
-                        .build();
-
-                    let markdown_text = match converter.convert(&html_content) {
-                        Ok(content) => content,
-                        Err(_) => String::from(""),
-                    };
-
-                    let page_data = WikiPageData {
-                        title: url.clone(),
-                        markdown_content: markdown_text.clone(),
-                    };
-                    caching_session.write_to_cache(&url.clone(), &page_data)?;
-                    Some(page_data)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        }
-    };
+    let page_data_response: Option<WikiPageData> =
+        fetch_through_cache(&url, &mut caching_session, |response| {
+            let html_content = response.text().ok()?;
+            Some(WikiPageData {
+                title: url.clone(),
+                markdown_content: markdown_of_html(&html_content),
+                infobox: parsing::extract_infobox(&html_content),
+            })
+        });
 
     match page_data_response {
         Some(page_data) => {
@@ -142,21 +332,149 @@ pub fn get_wikipedia_page(
             }
             let mut spans = parsing::parse_markdown(&page_data.markdown_content);
             spans = remove_unnecessary_spans(spans);
-            Ok(spans)
+            Ok(WikiPage {
+                spans,
+                infobox: page_data.infobox,
+            })
         }
         None => Err("Could not get page data".into()),
     }
 }
 
+/// Full-text search over the cached pages of `wiki`, ranking title matches above text matches
+fn search_cached_pages(
+    query: &str,
+    wiki: &WikiSource,
+    caching_session: &CachingSession,
+) -> Vec<SearchResult> {
+    let word_regexes: Vec<Regex> = query
+        .split_whitespace()
+        .filter_map(|word| Regex::new(&format!("(?i){}", regex::escape(word))).ok())
+        .collect();
+    if word_regexes.is_empty() {
+        return vec![];
+    }
+
+    let mut scored_results: Vec<(usize, SearchResult)> = caching_session
+        .page_texts
+        .iter()
+        .filter_map(|(url, plain_text)| {
+            let title = wiki.title_from_page_url(url)?;
+
+            let mut score = 0;
+            for word_regex in word_regexes.iter() {
+                let title_matches = word_regex.find_iter(&title).count();
+                let text_matches = word_regex.find_iter(plain_text).count();
+                if title_matches + text_matches == 0 {
+                    return None;
+                }
+                score += title_matches * 100 + text_matches;
+            }
+
+            let snippet = word_regexes
+                .iter()
+                .filter_map(|word_regex| word_regex.find(plain_text))
+                .min_by_key(|word_match| word_match.start())
+                .map(|word_match| {
+                    let start = floor_char_boundary(
+                        plain_text,
+                        word_match.start().saturating_sub(LOCAL_SNIPPET_CONTEXT),
+                    );
+                    let end =
+                        ceil_char_boundary(plain_text, word_match.end() + LOCAL_SNIPPET_CONTEXT);
+                    format!(
+                        "{}{OPENING_TAG}{}{CLOSING_TAG}{}",
+                        &plain_text[start..word_match.start()],
+                        word_match.as_str(),
+                        &plain_text[word_match.end()..end]
+                    )
+                })
+                .unwrap_or_default();
+
+            Some((
+                score,
+                SearchResult {
+                    title,
+                    pageid: 0,
+                    snippet,
+                    is_local: true,
+                },
+            ))
+        })
+        .collect();
+
+    scored_results.sort_by(|(score_a, _), (score_b, _)| score_b.cmp(score_a));
+    scored_results
+        .into_iter()
+        .take(SEARCH_RESULT_LIMIT as usize)
+        .map(|(_, result)| result)
+        .collect()
+}
+
+/// Reads the plain text of every cached page into the cache's search index, in the background.
+/// The cache is only locked to list its files and to hand over the finished index.
+pub fn load_cached_page_texts(cache: Shared<CachingSession>) {
+    thread::spawn(move || {
+        let cached_files = cache.lock().unwrap().cached_files();
+        let page_texts: HashMap<Url, String> = cached_files
+            .into_iter()
+            .filter_map(|(url, file_path)| {
+                let page_data = read_cache_file::<WikiPageData>(&file_path)?;
+                if page_data.markdown_content.starts_with("{") {
+                    return None;
+                }
+                Some((url, plain_text_of_markdown(&page_data.markdown_content)))
+            })
+            .collect();
+        let mut caching_session = cache.lock().unwrap();
+        // Leave out any page evicted while the index was being built
+        caching_session.page_texts = page_texts
+            .into_iter()
+            .filter(|(url, _)| caching_session.lookup_table.contains_key(url))
+            .collect();
+    });
+}
+
+fn plain_text_of_markdown(markdown: &str) -> String {
+    parsing::parse_markdown(markdown)
+        .iter()
+        .map(|formatted_span| match formatted_span.is_break {
+            true => " ",
+            false => formatted_span.text.as_str(),
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
 pub fn load_search_query_to_app(
     input: String,
     loading_flag: Shared<bool>,
     search_results: Shared<Vec<SearchResult>>,
+    wiki: WikiSource,
     cache: Shared<CachingSession>,
 ) {
     *loading_flag.lock().unwrap() = true;
     thread::spawn(move || {
-        if let Ok(mut results) = get_wikipedia_query(input.as_str(), cache) {
+        if let Ok(mut results) = get_wikipedia_query(input.as_str(), &wiki, cache) {
             for search_result in results.iter_mut() {
                 search_result.snippet = format!("...{}...", search_result.snippet);
             }
@@ -168,41 +486,37 @@ pub fn load_search_query_to_app(
 
 pub fn load_article_to_app(
     title: String,
-    has_loaded_flag: Shared<bool>,
-    markdown_spans: Shared<Vec<FormattedSpan>>,
-    link_indices: Shared<Vec<usize>>,
-    is_valid_page: Shared<bool>,
+    article: SharedArticle,
+    wiki: WikiSource,
     cache: Shared<CachingSession>,
 ) {
-    *has_loaded_flag.lock().unwrap() = false;
+    *article.has_loaded_flag.lock().unwrap() = false;
     thread::spawn(move || {
-        if let Ok(results) = get_wikipedia_page(title.as_str(), cache) {
-            *markdown_spans.lock().unwrap() = results.clone();
-            *link_indices.lock().unwrap() = results
-                .iter()
-                .filter(|formatted_span| {
-                    if let Some(_) = formatted_span.link {
-                        return true;
-                    } else {
-                        return false;
-                    }
-                })
-                .map(|formatted_span| formatted_span.index)
-                .collect::<Vec<usize>>();
-            *is_valid_page.lock().unwrap() = true;
+        if let Ok(page) = get_wikipedia_page(title.as_str(), &wiki, cache) {
+            *article.link_indices.lock().unwrap() = parsing::link_span_indices(&page.spans);
+            *article.markdown_spans.lock().unwrap() = page.spans;
+            *article.infobox.lock().unwrap() = page.infobox;
+            *article.is_valid_page.lock().unwrap() = true;
         } else {
-            *markdown_spans.lock().unwrap() = vec![FormattedSpan {
+            *article.markdown_spans.lock().unwrap() = vec![FormattedSpan {
                 index: 0,
                 text: String::from("Page not found."),
                 is_heading: true,
                 heading_level: 1,
                 link: None,
                 is_break: false,
+                table: None,
+                style: InlineStyle::default(),
+                list_item: None,
+                quote_depth: 0,
+                is_code_block: false,
             }];
-            *is_valid_page.lock().unwrap() = false;
-            *link_indices.lock().unwrap() = vec![];
+            *article.infobox.lock().unwrap() = vec![];
+            *article.is_valid_page.lock().unwrap() = false;
+            *article.link_indices.lock().unwrap() = vec![];
         }
-        *has_loaded_flag.lock().unwrap() = true;
+        *article.spans_generation.lock().unwrap() += 1;
+        *article.has_loaded_flag.lock().unwrap() = true;
     });
 }
 
@@ -229,3 +543,43 @@ pub fn remove_unnecessary_spans(mut spans: Vec<FormattedSpan>) -> Vec<FormattedS
 
     spans
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_titles_round_trip_through_page_urls() {
+        let wiki = WikiSource::from_url("localhost:8765/wiki/");
+        assert_eq!(wiki.name, "localhost:8765");
+
+        for title in [
+            "Rust",
+            "AC/DC",
+            "C++",
+            "100% Orange Juice",
+            "Why? #1",
+            "Zürich",
+        ] {
+            let url = wiki.page_url(title);
+            assert!(url.starts_with("https://localhost:8765/wiki/rest.php/v1/page/"));
+            assert_eq!(wiki.title_from_page_url(&url).as_deref(), Some(title));
+        }
+        assert_eq!(
+            wiki.page_url("AC/DC"),
+            "https://localhost:8765/wiki/rest.php/v1/page/AC%2FDC/html"
+        );
+
+        let other_wiki = WikiSource::from_url("http://localhost:8765");
+        assert_eq!(other_wiki.title_from_page_url(&wiki.page_url("Rust")), None);
+    }
+
+    #[test]
+    fn search_queries_are_encoded() {
+        let wiki = WikiSource::wikimedia_project("wikipedia", "en");
+        let url = reqwest::Url::parse(&wiki.search_url("Tom & Jerry=cats?")).unwrap();
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["srsearch"], "Tom & Jerry=cats?");
+        assert_eq!(query["list"], "search");
+    }
+}
@@ -0,0 +1,136 @@
+//! Snapshot tests of how saved Wikipedia pages are parsed and laid out.
+//! After an intended change, review the new snapshots with `cargo insta review`,
+//! or accept them all by running the tests with `INSTA_UPDATE=always`.
+
+use std::fs;
+
+use wik::article_layout::ArticleLayout;
+use wik::parsing::{self, FormattedSpan};
+use wik::wikipedia::{markdown_of_html, remove_unnecessary_spans};
+
+const LAYOUT_WIDTH: usize = 60;
+
+/// Parses a page saved in `tests/pages` the same way a fetched page is
+fn parse_sample_page(page_name: &str) -> Vec<FormattedSpan> {
+    let html_content = fs::read_to_string(format!(
+        "{}/tests/pages/{page_name}.html",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    remove_unnecessary_spans(parsing::parse_markdown(&markdown_of_html(&html_content)))
+}
+
+/// One line per span, giving everything that affects how it is drawn,
+/// followed by the spans that links can be selected at
+fn describe_spans(spans: &[FormattedSpan]) -> String {
+    let mut lines: Vec<String> = spans
+        .iter()
+        .map(|formatted_span| {
+            let mut description = format!("{:>3} ", formatted_span.index);
+            if formatted_span.is_break {
+                description.push_str("break");
+                return description;
+            }
+            if let Some(table) = &formatted_span.table {
+                description.push_str(&format!("table {:?}", table.rows));
+                return description;
+            }
+            if formatted_span.is_heading {
+                description.push_str(&format!("h{} ", formatted_span.heading_level));
+            }
+            description.push_str(&format!("{:?}", formatted_span.text));
+            if let Some(link) = &formatted_span.link {
+                description.push_str(&format!(" -> {link}"));
+            }
+            let style = formatted_span.style;
+            for (is_set, name) in [
+                (style.bold, "bold"),
+                (style.italic, "italic"),
+                (style.code, "code"),
+                (style.strikethrough, "strikethrough"),
+                (formatted_span.is_code_block, "code block"),
+            ] {
+                if is_set {
+                    description.push_str(&format!(" [{name}]"));
+                }
+            }
+            if let Some(list_item) = formatted_span.list_item {
+                description.push_str(&format!(" [list depth {}", list_item.depth));
+                if let Some(number) = list_item.number {
+                    description.push_str(&format!(" number {number}"));
+                }
+                if list_item.is_continuation {
+                    description.push_str(" continued");
+                }
+                description.push(']');
+            }
+            if formatted_span.quote_depth > 0 {
+                description.push_str(&format!(" [quote depth {}]", formatted_span.quote_depth));
+            }
+            description
+        })
+        .collect();
+    lines.push(format!("links at {:?}", parsing::link_span_indices(spans)));
+    lines.join("\n")
+}
+
+/// The page as it is drawn in an article view `width` columns wide
+fn render_layout(spans: &[FormattedSpan], width: usize) -> String {
+    ArticleLayout::new(spans, width)
+        .lines
+        .iter()
+        .map(|fragments| {
+            fragments
+                .iter()
+                .map(|fragment| fragment.text.as_str())
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn assert_page_snapshots(page_name: &str) {
+    let spans = parse_sample_page(page_name);
+    insta::assert_snapshot!(format!("{page_name}_spans"), describe_spans(&spans));
+    insta::assert_snapshot!(
+        format!("{page_name}_layout"),
+        render_layout(&spans, LAYOUT_WIDTH)
+    );
+}
+
+#[test]
+fn article_with_formatting_quotes_and_code() {
+    assert_page_snapshots("Lighthouse");
+}
+
+#[test]
+fn article_with_nested_lists_and_tables() {
+    assert_page_snapshots("List_of_rivers");
+}
+
+#[test]
+fn links_split_by_formatting_are_selected_as_one() {
+    let spans = parsing::parse_markdown("See [the **big** link](./Foo) and [another](./Bar).");
+    let link_indices = parsing::link_span_indices(&spans);
+    assert_eq!(link_indices.len(), 2);
+    let first_link_spans = parsing::link_span_group(&spans, link_indices[0]);
+    let first_link_text: String = first_link_spans
+        .iter()
+        .map(|&span_index| spans[span_index].text.as_str())
+        .collect();
+    assert_eq!(first_link_text, "the big link");
+}
+
+#[test]
+fn list_items_wrap_under_their_text() {
+    let spans =
+        parsing::parse_markdown("*   one two three four five six\n    *   seven eight nine");
+    insta::assert_snapshot!(render_layout(&spans, 16), @r"
+    • one two three
+      four five six
+      ◦ seven eight
+        nine
+    ");
+}
@@ -0,0 +1,48 @@
+//! Typing and editing in the text inputs, which move their cursor a whole character at a time.
+
+use wik::app::{CursorDirection, TitleState, TypeableState};
+
+fn empty_input() -> TitleState {
+    TitleState {
+        input: String::new(),
+        cursor_pos: 0,
+    }
+}
+
+#[test]
+fn typing_a_multibyte_query() {
+    let mut title = empty_input();
+    for c in "Zürich 日本".chars() {
+        title.type_char(c);
+    }
+    assert_eq!(title.input, "Zürich 日本");
+    assert_eq!(title.cursor_pos, title.input.len());
+
+    title.backspace();
+    assert_eq!(title.input, "Zürich 日");
+
+    // Back over "日", " ", "h", "c", "i", "r" to just after "ü"
+    for _ in 0..6 {
+        title.move_cursor_one_step(CursorDirection::LEFT);
+    }
+    assert_eq!(&title.input[..title.cursor_pos], "Zü");
+    title.type_char('é');
+    assert_eq!(title.input, "Züérich 日");
+    title.backspace();
+    title.backspace();
+    assert_eq!(title.input, "Zrich 日");
+    assert_eq!(&title.input[..title.cursor_pos], "Z");
+}
+
+#[test]
+fn cursor_stops_at_the_ends_of_the_input() {
+    let mut title = empty_input();
+    title.type_char('é');
+    title.move_cursor_one_step(CursorDirection::RIGHT);
+    assert_eq!(title.cursor_pos, "é".len());
+    title.move_cursor_one_step(CursorDirection::LEFT);
+    title.move_cursor_one_step(CursorDirection::LEFT);
+    assert_eq!(title.cursor_pos, 0);
+    title.backspace();
+    assert_eq!(title.input, "é");
+}